@@ -0,0 +1,41 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use mysql::binlog::{BinlogChecksum, BinlogEvent, BinlogEventHeader};
+
+// The input is a sequence of binlog event packets, each prefixed by a 2 bytes length, so that row events are decoded
+// with the columns of the table map event that precedes them, and checksummed according to the format description
+// event that precedes them.
+fuzz_target!(|data: &[u8]| {
+  let mut columns = None;
+  let mut checksum = BinlogChecksum::None;
+
+  let mut data = data;
+  while data.len() >= 2 {
+    let len = usize::from(u16::from_le_bytes([data[0], data[1]])).min(data.len() - 2);
+    let packet = Bytes::copy_from_slice(&data[2..2 + len]);
+    data = &data[2 + len..];
+
+    match BinlogEventHeader::parse(packet, checksum) {
+      Ok((_header, BinlogEvent::FormatDescription(event))) => checksum = event.checksum,
+      Ok((_header, BinlogEvent::TableMap(event))) => columns = event.columns().ok(),
+      Ok((_header, BinlogEvent::Insert(event))) => {
+        if let Some(columns) = &columns {
+          let _ = event.rows(columns);
+        }
+      }
+      Ok((_header, BinlogEvent::Update(event))) => {
+        if let Some(columns) = &columns {
+          let _ = event.rows(columns);
+        }
+      }
+      Ok((_header, BinlogEvent::Delete(event))) => {
+        if let Some(columns) = &columns {
+          let _ = event.rows(columns);
+        }
+      }
+      _ => {}
+    }
+  }
+});
@@ -1,7 +1,50 @@
 use super::constants::{CharacterSet, ColumnMetadataType, ColumnType};
+use super::error::ProtocolError;
+use super::gtid::{Gtid, GtidSet};
 use super::{buf_ext::BufExt, constants::BinlogEventType};
 use bytes::{Buf, Bytes};
-use std::io;
+use std::str::FromStr;
+use std::{fmt, io};
+use uuid::Uuid;
+
+const EVENT_HEADER_LEN: usize = 19;
+const CHECKSUM_LEN: usize = 4;
+
+// Checksum algorithm of the binlog events, `@@GLOBAL.binlog_checksum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinlogChecksum {
+  #[default]
+  None,
+  Crc32,
+}
+
+impl TryFrom<u8> for BinlogChecksum {
+  type Error = u8;
+
+  fn try_from(value: u8) -> Result<Self, Self::Error> {
+    match value {
+      // 0xFF is the algorithm of the events written by servers that do not support checksums.
+      0x00 | 0xFF => Ok(Self::None),
+      0x01 => Ok(Self::Crc32),
+      value => Err(value),
+    }
+  }
+}
+
+impl FromStr for BinlogChecksum {
+  type Err = io::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_uppercase().as_str() {
+      "NONE" => Ok(Self::None),
+      "CRC32" => Ok(Self::Crc32),
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid binlog_checksum {}", s),
+      )),
+    }
+  }
+}
 
 #[derive(Debug)]
 pub struct BinlogEventHeader {
@@ -9,38 +52,75 @@ pub struct BinlogEventHeader {
   pub server_id: u32,
   pub log_position: u32,
   pub flags: u16,
-  pub checksum: Bytes,
+  // CRC32 of the event, verified when parsing it.
+  pub checksum: Option<u32>,
 }
 
 impl BinlogEventHeader {
-  pub fn parse(mut b: Bytes) -> io::Result<(BinlogEventHeader, BinlogEvent)> {
+  // Parses a binlog event packet whose events are checksummed with `checksum`, except the format description events,
+  // which state their own algorithm. A `ProtocolError::ChecksumMismatch` is returned when the event is corrupted.
+  pub fn parse(mut b: Bytes, checksum: BinlogChecksum) -> io::Result<(BinlogEventHeader, BinlogEvent)> {
     // skip OK byte
-    assert_eq!(0x00, b.get_u8());
-
-    let timestamp = b.get_u32_le();
-    let event_type = b.get_u8().try_into().unwrap();
-    let server_id = b.get_u32_le();
-    b.advance(4); // skip event size
-    let log_position = b.get_u32_le();
-    let flags = b.get_u16_le();
-    let payload_len = b.remaining() - 4; // TODO: checksum is usually 4 bytes, but can be changed...
-    let payload = b.split_to(payload_len);
-    let checksum = b.clone();
+    match b.mysql_get_u8()? {
+      0x00 => {}
+      header => return Err(ProtocolError::UnexpectedPacket(header).into()),
+    }
+
+    let event = b.clone();
+    let timestamp = b.mysql_get_u32_le()?;
+    let event_type = b.mysql_get_enum("binlog event type")?;
+    let server_id = b.mysql_get_u32_le()?;
+    let event_size = b.mysql_get_u32_le()? as usize;
+    let log_position = b.mysql_get_u32_le()?;
+    let flags = b.mysql_get_u16_le()?;
+
+    // The event size includes the header and the checksum.
+    let payload_len = event_size
+      .checked_sub(EVENT_HEADER_LEN)
+      .ok_or_else(|| ProtocolError::invalid(format!("invalid binlog event size {}", event_size)))?;
+    b.mysql_ensure_remaining(payload_len)?;
+    if b.remaining() != payload_len {
+      return Err(ProtocolError::invalid(format!("binlog event is longer than its size {}", event_size)).into());
+    }
+
+    // The format description event ends with the checksum algorithm of the binlog file followed by its own checksum,
+    // even when the algorithm is NONE.
+    let is_format_description = event_type == BinlogEventType::FORMAT_DESCRIPTION_EVENT;
+    let (payload, checksum_value) = if is_format_description || checksum == BinlogChecksum::Crc32 {
+      b.mysql_ensure_remaining(CHECKSUM_LEN)?;
+      let payload = b.split_to(b.remaining() - CHECKSUM_LEN);
+      (payload, Some(b.get_u32_le()))
+    } else {
+      (b, None)
+    };
+
+    let checksum = if is_format_description {
+      FormatDescriptionEvent::parse_checksum(&payload)?
+    } else {
+      checksum
+    };
+    let checksum = match (checksum, checksum_value) {
+      (BinlogChecksum::Crc32, Some(expected)) => {
+        let computed = crc32fast::hash(&event[..event.len() - CHECKSUM_LEN]);
+        if computed != expected {
+          return Err(ProtocolError::ChecksumMismatch { expected, computed }.into());
+        }
+        Some(expected)
+      }
+      _ => None,
+    };
 
     let header = BinlogEventHeader {
       timestamp,
       server_id,
       log_position,
       flags,
-      checksum: checksum.clone(),
+      checksum,
     };
 
     let event = match event_type {
       BinlogEventType::TABLE_MAP_EVENT => TableMapEvent::parse(payload).map(BinlogEvent::TableMap),
-      BinlogEventType::ROTATE_EVENT => {
-        // NOTE: Strangely enough, the checksum is actually the suffix of the binlog file name.
-        RotateEvent::parse(payload, checksum).map(BinlogEvent::Rotate)
-      }
+      BinlogEventType::ROTATE_EVENT => RotateEvent::parse(payload).map(BinlogEvent::Rotate),
       BinlogEventType::FORMAT_DESCRIPTION_EVENT => {
         FormatDescriptionEvent::parse(payload).map(BinlogEvent::FormatDescription)
       }
@@ -56,7 +136,7 @@ impl BinlogEventHeader {
       BinlogEventType::XID_EVENT => XidEvent::parse(payload).map(BinlogEvent::Xid),
       BinlogEventType::GTID_EVENT => GtidEvent::parse(payload).map(BinlogEvent::Gtid),
       BinlogEventType::PREVIOUS_GTIDS_EVENT => PreviousGtidEvent::parse(payload).map(BinlogEvent::PreviousGtid),
-      BinlogEventType::ANONYMOUS_GTID_EVENT => AnonymousGtidEvent::parse(payload).map(BinlogEvent::AnonymousGtid),
+      BinlogEventType::ANONYMOUS_GTID_EVENT => GtidEvent::parse(payload).map(BinlogEvent::AnonymousGtid),
       not_supported => Ok(BinlogEvent::NotSupported(not_supported)),
     }?;
 
@@ -75,7 +155,8 @@ pub enum BinlogEvent {
   Xid(XidEvent),
   Gtid(GtidEvent),
   PreviousGtid(PreviousGtidEvent),
-  AnonymousGtid(AnonymousGtidEvent),
+  // Transaction without GTID, when `gtid_mode` is OFF. Its GTID is zero.
+  AnonymousGtid(GtidEvent),
   NotSupported(BinlogEventType),
 }
 
@@ -86,14 +167,13 @@ pub struct RotateEvent {
 }
 
 impl RotateEvent {
-  fn parse(mut b: Bytes, cb: Bytes) -> io::Result<Self> {
-    let next_log_position = b.get_u64_le() as u32;
-    let next_log_file_buffer = &[&b[..], &cb[..]].concat();
-    let next_log_file = std::str::from_utf8(next_log_file_buffer).unwrap();
+  fn parse(mut b: Bytes) -> io::Result<Self> {
+    let next_log_position = b.mysql_get_u64_le()? as u32;
+    let next_log_file = b.mysql_get_eof_string()?;
 
     Ok(Self {
       next_log_position,
-      next_log_file: next_log_file.into(),
+      next_log_file,
     })
   }
 }
@@ -128,17 +208,21 @@ pub struct TableMapEventMetadata {
 
 impl TableMapEventMetadata {
   fn parse(mut b: Bytes) -> io::Result<Self> {
+    fn parse_charset(b: &mut Bytes) -> io::Result<CharacterSet> {
+      let charset = b.mysql_get_lenc_uint()?;
+      u8::try_from(charset)
+        .ok()
+        .and_then(|v| CharacterSet::try_from(v).ok())
+        .ok_or_else(|| ProtocolError::unknown("character set", charset).into())
+    }
+
     fn parse_default_charset(mut b: Bytes) -> io::Result<(CharacterSet, Vec<(usize, CharacterSet)>)> {
-      let default_charset = b.mysql_get_lenc_uint();
-      let default_charset = (default_charset as u8).try_into().unwrap();
+      let default_charset = parse_charset(&mut b)?;
 
       let mut pairs = Vec::new();
       while b.remaining() > 0 {
-        let index = b.mysql_get_lenc_uint().try_into().unwrap();
-
-        let charset = b.mysql_get_lenc_uint();
-        let charset = (charset as u8).try_into().unwrap();
-
+        let index = b.mysql_get_lenc_uint()? as usize;
+        let charset = parse_charset(&mut b)?;
         pairs.push((index, charset))
       }
       Ok((default_charset, pairs))
@@ -147,27 +231,27 @@ impl TableMapEventMetadata {
     fn parse_column_charsets(mut b: Bytes) -> io::Result<Vec<CharacterSet>> {
       let mut column_charsets = Vec::new();
       while b.remaining() > 0 {
-        let column_charset = b.mysql_get_lenc_uint();
-        let column_charset = (column_charset as u8).try_into().unwrap();
-        column_charsets.push(column_charset);
+        column_charsets.push(parse_charset(&mut b)?);
       }
       Ok(column_charsets)
     }
 
     fn parse_strings(mut b: Bytes) -> io::Result<Vec<String>> {
-      let length = b.mysql_get_lenc_uint().try_into().unwrap();
+      let length = b.mysql_get_lenc_len()?;
       let mut strings = Vec::with_capacity(length);
       for _i in 0..length {
-        strings.push(b.mysql_get_lenc_string());
+        strings.push(b.mysql_get_lenc_string()?);
+      }
+      if b.has_remaining() {
+        return Err(ProtocolError::invalid("trailing bytes after metadata strings").into());
       }
-      assert_eq!(0, b.remaining());
       Ok(strings)
     }
 
     fn parse_ints(mut b: Bytes) -> io::Result<Vec<u64>> {
       let mut ints = Vec::new();
       while b.remaining() > 0 {
-        ints.push(b.mysql_get_lenc_uint());
+        ints.push(b.mysql_get_lenc_uint()?);
       }
       Ok(ints)
     }
@@ -175,7 +259,7 @@ impl TableMapEventMetadata {
     fn parse_column_names(mut b: Bytes) -> io::Result<Vec<String>> {
       let mut column_names = Vec::new();
       while b.remaining() > 0 {
-        column_names.push(b.mysql_get_lenc_string());
+        column_names.push(b.mysql_get_lenc_string()?);
       }
       Ok(column_names)
     }
@@ -183,8 +267,8 @@ impl TableMapEventMetadata {
     fn parse_keys_with_prefixes(mut b: Bytes) -> io::Result<Vec<(u64, u64)>> {
       let mut primary_keys_with_prefixes = Vec::new();
       while b.remaining() > 0 {
-        let index = b.mysql_get_lenc_uint();
-        let prefix = b.mysql_get_lenc_uint();
+        let index = b.mysql_get_lenc_uint()?;
+        let prefix = b.mysql_get_lenc_uint()?;
         primary_keys_with_prefixes.push((index, prefix));
       }
       Ok(primary_keys_with_prefixes)
@@ -193,8 +277,8 @@ impl TableMapEventMetadata {
     let mut metadata = Self::default();
 
     while b.remaining() > 0 {
-      let metadata_type: ColumnMetadataType = b.get_u8().try_into().unwrap();
-      let metadata_len = b.mysql_get_lenc_uint().try_into().unwrap();
+      let metadata_type: ColumnMetadataType = b.mysql_get_enum("column metadata type")?;
+      let metadata_len = b.mysql_get_lenc_len()?;
       let metadata_value = b.split_to(metadata_len);
 
       // https://github.com/mysql/mysql-server/blob/8.0/libbinlogevents/src/rows_event.cpp#L141
@@ -250,30 +334,28 @@ impl TableMapEventMetadata {
 
 impl TableMapEvent {
   fn parse(mut b: Bytes) -> io::Result<Self> {
-    let table_id = b.get_uint_le(6); // this is actually a fixed length (either 4 or 6 bytes)
-    let flags = b.get_u16_le();
+    let table_id = b.mysql_get_uint_le(6)?; // this is actually a fixed length (either 4 or 6 bytes)
+    let flags = b.mysql_get_u16_le()?;
 
-    let schema_len = b.get_u8().try_into().unwrap();
-    let schema = b.split_to(schema_len);
-    let schema = std::str::from_utf8(schema.chunk()).unwrap();
+    let schema_len = b.mysql_get_u8()?.into();
+    let schema = b.mysql_get_fixed_length_string(schema_len)?;
 
     // skip 0x00
-    assert_eq!(0x00, b.get_u8());
+    expect_null_byte(&mut b)?;
 
-    let table_len = b.mysql_get_lenc_uint().try_into().unwrap();
-    let table = b.split_to(table_len);
-    let table = std::str::from_utf8(table.chunk()).unwrap();
+    let table_len = b.mysql_get_lenc_len()?;
+    let table = b.mysql_get_fixed_length_string(table_len)?;
 
     // skip 0x00
-    assert_eq!(0x00, b.get_u8());
+    expect_null_byte(&mut b)?;
 
-    let column_count = b.mysql_get_lenc_uint().try_into().unwrap();
+    let column_count = b.mysql_get_lenc_len()?;
     let mut column_types = Vec::with_capacity(column_count);
     for _ in 0..column_count {
-      column_types.push(b.get_u8().try_into().unwrap());
+      column_types.push(b.mysql_get_enum("column type")?);
     }
 
-    let column_metas_buffer_len = b.mysql_get_lenc_uint().try_into().unwrap();
+    let column_metas_buffer_len = b.mysql_get_lenc_len()?;
     let mut column_metas_buffer = b.split_to(column_metas_buffer_len);
     let mut column_metas = vec![0; column_count];
 
@@ -284,8 +366,11 @@ impl TableMapEvent {
         | ColumnType::MYSQL_TYPE_DOUBLE
         | ColumnType::MYSQL_TYPE_BLOB
         | ColumnType::MYSQL_TYPE_GEOMETRY
-        | ColumnType::MYSQL_TYPE_JSON => {
-          column_metas[i] = column_metas_buffer.get_u8().into();
+        | ColumnType::MYSQL_TYPE_JSON
+        | ColumnType::MYSQL_TYPE_TIMESTAMP2
+        | ColumnType::MYSQL_TYPE_DATETIME2
+        | ColumnType::MYSQL_TYPE_TIME2 => {
+          column_metas[i] = column_metas_buffer.mysql_get_u8()?.into();
         }
 
         ColumnType::MYSQL_TYPE_VARCHAR
@@ -293,7 +378,7 @@ impl TableMapEvent {
         | ColumnType::MYSQL_TYPE_VAR_STRING
         | ColumnType::MYSQL_TYPE_STRING
         | ColumnType::MYSQL_TYPE_NEWDECIMAL => {
-          column_metas[i] = column_metas_buffer.get_u16_le().into();
+          column_metas[i] = column_metas_buffer.mysql_get_u16_le()?.into();
         }
 
         ColumnType::MYSQL_TYPE_DECIMAL
@@ -311,30 +396,30 @@ impl TableMapEvent {
           column_metas[i] = 0;
         }
 
-        ColumnType::MYSQL_TYPE_TIMESTAMP2
-        | ColumnType::MYSQL_TYPE_DATETIME2
-        | ColumnType::MYSQL_TYPE_TIME2
-        | ColumnType::MYSQL_TYPE_ENUM
+        ColumnType::MYSQL_TYPE_ENUM
         | ColumnType::MYSQL_TYPE_SET
         | ColumnType::MYSQL_TYPE_TINY_BLOB
         | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
         | ColumnType::MYSQL_TYPE_LONG_BLOB => {
-          unreachable!()
+          // These types only exist on the server, the binlog records them as MYSQL_TYPE_STRING or MYSQL_TYPE_BLOB.
+          return Err(ProtocolError::unknown("table map column type", *t as u8).into());
         }
       }
     }
 
-    assert_eq!(column_metas_buffer.remaining(), 0);
-    let bitmap_len = (column_count + 7) / 8;
-    let null_bitmap = b.split_to(bitmap_len);
+    if column_metas_buffer.has_remaining() {
+      return Err(ProtocolError::invalid("trailing bytes after column metadata").into());
+    }
+    let bitmap_len = column_count.div_ceil(8);
+    let null_bitmap = b.mysql_get_bytes(bitmap_len)?;
 
     let metadata = TableMapEventMetadata::parse(b)?;
 
     Ok(Self {
       table_id,
       flags,
-      schema: schema.into(),
-      table: table.into(),
+      schema,
+      table,
       column_count,
       column_types,
       column_metas,
@@ -343,149 +428,157 @@ impl TableMapEvent {
     })
   }
 
-  pub fn columns(&self) -> Vec<Column> {
-    (0..self.column_count)
-      .scan(0, |j, i| {
-        let column_name = self.metadata.column_names.as_ref().unwrap()[i].clone();
-        let column_type = self.column_types[i];
-        let column_meta = self.column_metas[i];
-
-        // SCAN from LSB to MSB
-        let is_nullable = self.null_bitmap[i / 8] & (1 << (i % 8)) != 0;
-
-        let column_type_definition = match column_type {
-          ColumnType::MYSQL_TYPE_TINY
-          | ColumnType::MYSQL_TYPE_SHORT
-          | ColumnType::MYSQL_TYPE_INT24
-          | ColumnType::MYSQL_TYPE_LONG
-          | ColumnType::MYSQL_TYPE_LONGLONG => {
-            // SCAN from MSB to LSB
-            let is_unsigned =
-              self.metadata.is_unsigned_integer_bitmap.as_ref().unwrap()[*j / 8] & (0x80 >> (*j % 8)) != 0;
-
-            let t = match column_type {
-              ColumnType::MYSQL_TYPE_TINY if is_unsigned => ColumnTypeDefinition::U64 { pack_length: 1 },
-              ColumnType::MYSQL_TYPE_TINY => ColumnTypeDefinition::I64 { pack_length: 1 },
-              ColumnType::MYSQL_TYPE_SHORT if is_unsigned => ColumnTypeDefinition::U64 { pack_length: 2 },
-              ColumnType::MYSQL_TYPE_SHORT => ColumnTypeDefinition::I64 { pack_length: 2 },
-              ColumnType::MYSQL_TYPE_INT24 if is_unsigned => ColumnTypeDefinition::U64 { pack_length: 3 },
-              ColumnType::MYSQL_TYPE_INT24 => ColumnTypeDefinition::I64 { pack_length: 3 },
-              ColumnType::MYSQL_TYPE_LONG if is_unsigned => ColumnTypeDefinition::U64 { pack_length: 4 },
-              ColumnType::MYSQL_TYPE_LONG => ColumnTypeDefinition::I64 { pack_length: 4 },
-              ColumnType::MYSQL_TYPE_LONGLONG if is_unsigned => ColumnTypeDefinition::U64 { pack_length: 8 },
-              ColumnType::MYSQL_TYPE_LONGLONG => ColumnTypeDefinition::I64 { pack_length: 8 },
-              _ => unreachable!(),
-            };
-
-            *j += 1;
-
-            t
-          }
+  pub fn columns(&self) -> io::Result<Vec<Column>> {
+    // Column names are only written when binlog_row_metadata=FULL.
+    let column_names = self
+      .metadata
+      .column_names
+      .as_ref()
+      .filter(|column_names| column_names.len() == self.column_count)
+      .ok_or_else(|| ProtocolError::invalid("table map event does not have the name of every column"))?;
+
+    // Integer columns are listed in the signedness bitmap in the order they appear, from MSB to LSB.
+    let signedness = self.metadata.is_unsigned_integer_bitmap.as_ref();
+    let mut integers = 0;
+    let mut integer = |pack_length| -> io::Result<ColumnTypeDefinition> {
+      let bits = signedness.and_then(|bitmap| bitmap.get(integers / 8)).ok_or_else(|| {
+        ProtocolError::invalid("table map event does not have the signedness of every integer column")
+      })?;
+      let is_unsigned = bits & (0x80 >> (integers % 8)) != 0;
+      integers += 1;
+      Ok(if is_unsigned {
+        ColumnTypeDefinition::U64 { pack_length }
+      } else {
+        ColumnTypeDefinition::I64 { pack_length }
+      })
+    };
 
-          ColumnType::MYSQL_TYPE_DECIMAL => unreachable!(),
-          ColumnType::MYSQL_TYPE_NEWDECIMAL => {
-            let bytes = column_meta.to_le_bytes();
-            let precision = bytes[0];
-            let scale = bytes[1];
-            ColumnTypeDefinition::Decimal { precision, scale }
+    let mut columns = Vec::with_capacity(self.column_count);
+    for (i, column_name) in column_names.iter().enumerate() {
+      let column_type = self.column_types[i];
+      let column_meta = self.column_metas[i];
+
+      // SCAN from LSB to MSB
+      let is_nullable = self.null_bitmap[i / 8] & (1 << (i % 8)) != 0;
+
+      let column_type_definition = match column_type {
+        ColumnType::MYSQL_TYPE_TINY => integer(1)?,
+        ColumnType::MYSQL_TYPE_SHORT => integer(2)?,
+        ColumnType::MYSQL_TYPE_INT24 => integer(3)?,
+        ColumnType::MYSQL_TYPE_LONG => integer(4)?,
+        ColumnType::MYSQL_TYPE_LONGLONG => integer(8)?,
+
+        ColumnType::MYSQL_TYPE_NEWDECIMAL => {
+          let bytes = column_meta.to_le_bytes();
+          let precision = bytes[0];
+          let scale = bytes[1];
+          if precision == 0 || precision > 65 || scale > 30 || scale > precision {
+            return Err(ProtocolError::invalid(format!("invalid decimal({}, {})", precision, scale)).into());
           }
+          ColumnTypeDefinition::Decimal { precision, scale }
+        }
 
-          ColumnType::MYSQL_TYPE_FLOAT => {
-            let pack_length = column_meta.try_into().unwrap();
-            assert_eq!(pack_length, 4); // Make sure that the server sizeof(float) == 4
-            ColumnTypeDefinition::F64 { pack_length }
-          }
-          ColumnType::MYSQL_TYPE_DOUBLE => {
-            let pack_length = column_meta.try_into().unwrap();
-            assert_eq!(pack_length, 8); // Make sure that the server sizeof(float) == 8
-            ColumnTypeDefinition::F64 { pack_length }
-          }
+        // Make sure that the server sizeof(float) == 4 and sizeof(double) == 8
+        ColumnType::MYSQL_TYPE_FLOAT if column_meta == 4 => ColumnTypeDefinition::F64 { pack_length: 4 },
+        ColumnType::MYSQL_TYPE_DOUBLE if column_meta == 8 => ColumnTypeDefinition::F64 { pack_length: 8 },
+        ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => {
+          return Err(ProtocolError::invalid(format!("invalid {:?} size {}", column_type, column_meta)).into())
+        }
 
-          ColumnType::MYSQL_TYPE_BLOB => {
-            let pack_length = column_meta.try_into().unwrap();
-            assert!(pack_length <= 4);
-            ColumnTypeDefinition::Blob { pack_length }
-          }
+        ColumnType::MYSQL_TYPE_BLOB if (1..=4).contains(&column_meta) => ColumnTypeDefinition::Blob {
+          pack_length: column_meta as usize,
+        },
+        ColumnType::MYSQL_TYPE_BLOB => {
+          return Err(ProtocolError::invalid(format!("invalid blob length size {}", column_meta)).into())
+        }
 
-          ColumnType::MYSQL_TYPE_DATE => ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U24),
-          ColumnType::MYSQL_TYPE_DATETIME => ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U64),
-          ColumnType::MYSQL_TYPE_DATETIME2 => {
-            ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::Arbitrary(column_meta.try_into().unwrap()))
-          }
-          ColumnType::MYSQL_TYPE_TIME => ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::U24),
-          ColumnType::MYSQL_TYPE_TIME2 => {
-            ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::Arbitrary(column_meta.try_into().unwrap()))
-          }
-          ColumnType::MYSQL_TYPE_YEAR => ColumnTypeDefinition::Year,
-          ColumnType::MYSQL_TYPE_TIMESTAMP => ColumnTypeDefinition::Timestamp,
-          ColumnType::MYSQL_TYPE_TIMESTAMP2 => todo!(),
+        ColumnType::MYSQL_TYPE_DATE => ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U24),
+        ColumnType::MYSQL_TYPE_DATETIME => ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U64),
+        ColumnType::MYSQL_TYPE_DATETIME2 => {
+          ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::Arbitrary(column_meta as u8))
+        }
+        ColumnType::MYSQL_TYPE_TIME => ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::U24),
+        ColumnType::MYSQL_TYPE_TIME2 => {
+          ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::Arbitrary(column_meta as u8))
+        }
+        ColumnType::MYSQL_TYPE_YEAR => ColumnTypeDefinition::Year,
+        ColumnType::MYSQL_TYPE_TIMESTAMP => ColumnTypeDefinition::Timestamp,
 
-          ColumnType::MYSQL_TYPE_JSON => {
-            let pack_length = column_meta.try_into().unwrap();
-            ColumnTypeDefinition::Json { pack_length }
-          }
-          ColumnType::MYSQL_TYPE_ENUM => unreachable!(),
-          ColumnType::MYSQL_TYPE_SET => unreachable!(),
-          ColumnType::MYSQL_TYPE_NULL => {
-            unreachable!()
+        ColumnType::MYSQL_TYPE_JSON => ColumnTypeDefinition::Json {
+          pack_length: column_meta as usize,
+        },
+        ColumnType::MYSQL_TYPE_BIT => {
+          let bytes = column_meta.to_le_bytes();
+          let useless = bytes[0];
+          let pack_length = bytes[1];
+          if useless != 0 || pack_length > 8 {
+            return Err(ProtocolError::invalid(format!("invalid bit metadata {}", column_meta)).into());
           }
-          ColumnType::MYSQL_TYPE_TINY_BLOB | ColumnType::MYSQL_TYPE_MEDIUM_BLOB | ColumnType::MYSQL_TYPE_LONG_BLOB => {
-            unreachable!()
+          ColumnTypeDefinition::U64 {
+            pack_length: pack_length.into(),
           }
-          ColumnType::MYSQL_TYPE_BIT => {
+        }
+        ColumnType::MYSQL_TYPE_VARCHAR => {
+          let pack_length = if column_meta > 255 { 2 } else { 1 };
+          ColumnTypeDefinition::String { pack_length }
+        }
+        ColumnType::MYSQL_TYPE_STRING => {
+          // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Table__map__event.html
+          // The first byte is always MYSQL_TYPE_VAR_STRING (i.e., 253). The second byte is the field size, i.e., the number of bytes in the representation of size of the string: 3 or 4.
+          // https://github.com/mysql/mysql-server/blob/9c3a49ec84b521cb0b35383f119099b2eb25d4ff/sql/log_event.cc#L1988-L2006
+
+          if column_meta > 255 {
             let bytes = column_meta.to_le_bytes();
-            let useless = bytes[0];
-            assert_eq!(0, useless);
-            let pack_length = bytes[1];
-            assert!(pack_length <= 8);
-            let pack_length = pack_length.try_into().unwrap();
-            ColumnTypeDefinition::U64 { pack_length }
-          }
-          ColumnType::MYSQL_TYPE_VARCHAR => {
-            let pack_length = if column_meta > 255 { 2 } else { 1 };
-            ColumnTypeDefinition::String { pack_length }
-          }
-          ColumnType::MYSQL_TYPE_VAR_STRING => {
-            // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Table__map__event.html
-            // This is used to store both strings and enumeration values. The first byte is a enumeration value storing the real type, which may be either MYSQL_TYPE_VAR_STRING or MYSQL_TYPE_ENUM. The second byte is a 1 byte unsigned integer representing the field size, i.e., the number of bytes needed to store the length of the string.
-            todo!()
-          }
-          ColumnType::MYSQL_TYPE_STRING => {
-            // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Table__map__event.html
-            // The first byte is always MYSQL_TYPE_VAR_STRING (i.e., 253). The second byte is the field size, i.e., the number of bytes in the representation of size of the string: 3 or 4.
-            // https://github.com/mysql/mysql-server/blob/9c3a49ec84b521cb0b35383f119099b2eb25d4ff/sql/log_event.cc#L1988-L2006
-
-            if column_meta > 255 {
-              let bytes = column_meta.to_le_bytes();
-
-              if bytes[0] & 0x30 != 0x30 {
-                let pack_length = if bytes[1] as u16 | (((bytes[0] as u16 & 0x30) ^ 0x30) << 4) > 255 {
-                  2
-                } else {
-                  1
-                };
-                ColumnTypeDefinition::String { pack_length }
+
+            if bytes[0] & 0x30 != 0x30 {
+              let pack_length = if bytes[1] as u16 | (((bytes[0] as u16 & 0x30) ^ 0x30) << 4) > 255 {
+                2
               } else {
-                match bytes[0] {
-                  0xF7 => ColumnTypeDefinition::Enum,
-                  0xF8 => ColumnTypeDefinition::Set,
-                  _ => ColumnTypeDefinition::String { pack_length: 1 },
-                }
-              }
+                1
+              };
+              ColumnTypeDefinition::String { pack_length }
             } else {
-              ColumnTypeDefinition::String { pack_length: 1 }
+              match bytes[0] {
+                0xF7 => ColumnTypeDefinition::Enum,
+                0xF8 => ColumnTypeDefinition::Set,
+                _ => ColumnTypeDefinition::String { pack_length: 1 },
+              }
             }
+          } else {
+            ColumnTypeDefinition::String { pack_length: 1 }
           }
-          ColumnType::MYSQL_TYPE_GEOMETRY => todo!(),
-        };
-
-        Some(Column {
-          column_name,
-          is_nullable,
-          column_type_definition,
-        })
-      })
-      .collect()
+        }
+
+        // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Table__map__event.html
+        // MYSQL_TYPE_VAR_STRING is used to store both strings and enumeration values. The first byte is a enumeration value storing the real type, which may be either MYSQL_TYPE_VAR_STRING or MYSQL_TYPE_ENUM. The second byte is a 1 byte unsigned integer representing the field size, i.e., the number of bytes needed to store the length of the string.
+        ColumnType::MYSQL_TYPE_DECIMAL
+        | ColumnType::MYSQL_TYPE_TIMESTAMP2
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_GEOMETRY => {
+          return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{:?} columns are not supported", column_type),
+          ))
+        }
+
+        // These types only exist on the server, the binlog records them as MYSQL_TYPE_STRING or MYSQL_TYPE_BLOB.
+        ColumnType::MYSQL_TYPE_NULL
+        | ColumnType::MYSQL_TYPE_ENUM
+        | ColumnType::MYSQL_TYPE_SET
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB => {
+          return Err(ProtocolError::unknown("table map column type", column_type as u8).into())
+        }
+      };
+
+      columns.push(Column {
+        column_name: column_name.clone(),
+        is_nullable,
+        column_type_definition,
+      });
+    }
+    Ok(columns)
   }
 }
 
@@ -496,16 +589,21 @@ pub struct FormatDescriptionEvent {
   pub create_timestamp: u32,
   pub event_header_length: u8,
   pub event_type_header_lengths: Bytes,
+  // Checksum algorithm of the events of the binlog file.
+  pub checksum: BinlogChecksum,
 }
 
 impl FormatDescriptionEvent {
+  // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Format__description__event.html
   fn parse(mut b: Bytes) -> io::Result<Self> {
-    let version = b.get_u16_le();
-    let _server_version = b.split_to(50);
-    let server_version = b.mysql_get_null_terminated_string();
-    let create_timestamp = b.get_u32_le();
-    let event_header_length = b.get_u8();
-    let event_type_header_lengths = b;
+    let version = b.mysql_get_u16_le()?;
+    let server_version = b.mysql_get_bytes(50)?;
+    let server_version = server_version.split(|v| *v == 0).next().unwrap_or_default();
+    let server_version = String::from_utf8(server_version.to_vec()).map_err(|_| ProtocolError::InvalidUtf8)?;
+    let create_timestamp = b.mysql_get_u32_le()?;
+    let event_header_length = b.mysql_get_u8()?;
+    let checksum = Self::parse_checksum(&b)?;
+    let event_type_header_lengths = b.split_to(b.len() - 1);
 
     Ok(Self {
       version,
@@ -513,8 +611,15 @@ impl FormatDescriptionEvent {
       create_timestamp,
       event_header_length,
       event_type_header_lengths,
+      checksum,
     })
   }
+
+  // The checksum algorithm is the last byte of the payload (mysql >= 5.6.1).
+  fn parse_checksum(b: &Bytes) -> io::Result<BinlogChecksum> {
+    let checksum = *b.last().ok_or(ProtocolError::UnexpectedEof)?;
+    BinlogChecksum::try_from(checksum).map_err(|v| ProtocolError::unknown("binlog checksum algorithm", v).into())
+  }
 }
 
 #[derive(Debug)]
@@ -537,9 +642,9 @@ impl InsertRowEvent {
       flags,
       extras,
       column_count,
-    } = RowEventHeader::parse(&mut b, use_extras);
-    let bitmap_len = (column_count + 7) / 8;
-    let columns_after_image = b.split_to(bitmap_len);
+    } = RowEventHeader::parse(&mut b, use_extras)?;
+    let bitmap_len = column_count.div_ceil(8);
+    let columns_after_image = b.mysql_get_bytes(bitmap_len)?;
     let rows = b;
     Ok(Self {
       table_id,
@@ -551,7 +656,7 @@ impl InsertRowEvent {
     })
   }
 
-  pub fn rows(&self, columns: &[Column]) -> Vec<Value> {
+  pub fn rows(&self, columns: &[Column]) -> io::Result<Vec<Value>> {
     parse_rows(&mut self.rows.clone(), columns, &self.columns_after_image)
   }
 }
@@ -564,34 +669,60 @@ struct RowEventHeader {
 }
 
 impl RowEventHeader {
-  pub fn parse(b: &mut Bytes, use_extras: bool) -> Self {
-    let table_id = b.get_uint_le(6);
-    let flags = b.get_u16_le();
+  pub fn parse(b: &mut Bytes, use_extras: bool) -> io::Result<Self> {
+    let table_id = b.mysql_get_uint_le(6)?;
+    let flags = b.mysql_get_u16_le()?;
     let mut extras = None;
     if use_extras {
-      let extras_len = (b.get_u16_le() - 2).try_into().unwrap();
-      extras = Some(b.split_to(extras_len))
+      // The length includes the 2 bytes of the length itself.
+      let extras_len = b
+        .mysql_get_u16_le()?
+        .checked_sub(2)
+        .ok_or_else(|| ProtocolError::invalid("invalid row event extra data length"))?;
+      extras = Some(b.mysql_get_bytes(extras_len.into())?)
     }
-    let column_count = b.mysql_get_lenc_uint().try_into().unwrap();
-    RowEventHeader {
+    let column_count = b.mysql_get_lenc_uint()? as usize;
+    Ok(RowEventHeader {
       table_id,
       flags,
       extras,
       column_count,
-    }
+    })
+  }
+}
+
+fn expect_null_byte(b: &mut Bytes) -> io::Result<()> {
+  match b.mysql_get_u8()? {
+    0x00 => Ok(()),
+    v => Err(ProtocolError::invalid(format!("expected a null byte, got 0x{:02X}", v)).into()),
   }
 }
 
-fn parse_rows(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<Value> {
+fn parse_rows(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> io::Result<Vec<Value>> {
   let mut rows = vec![];
   while b.remaining() > 0 {
-    rows.append(&mut parse_row(b, columns, _column_present));
+    rows.append(&mut parse_row(b, columns, _column_present)?);
   }
-  rows
+  Ok(rows)
 }
 
-fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<Value> {
-  let null_bitmap = b.split_to(_column_present.len());
+fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> io::Result<Vec<Value>> {
+  // A row always starts with its null bitmap, which guarantees that reading rows makes progress.
+  if _column_present.is_empty() {
+    return Err(ProtocolError::invalid("row event has no columns").into());
+  }
+  let null_bitmap = b.mysql_get_bytes(_column_present.len())?;
+
+  fn narrow<T: TryFrom<u64>>(v: u64) -> io::Result<T> {
+    T::try_from(v).map_err(|_| ProtocolError::invalid(format!("invalid date or time component {}", v)).into())
+  }
+
+  fn unsupported(column_type_definition: &ColumnTypeDefinition) -> io::Error {
+    io::Error::new(
+      io::ErrorKind::Unsupported,
+      format!("{:?} values are not supported", column_type_definition),
+    )
+  }
 
   columns
     .iter()
@@ -603,50 +734,49 @@ fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<
         ..
       } = c;
 
-      let is_null = null_bitmap[i / 8] & (1 << (i % 8)) != 0;
+      let is_null = null_bitmap
+        .get(i / 8)
+        .ok_or_else(|| ProtocolError::invalid("row event has less columns than its table"))?
+        & (1 << (i % 8))
+        != 0;
 
       if *is_nullable && is_null {
-        return Value::Null;
+        return Ok(Value::Null);
       }
 
-      match column_type_definition {
-        ColumnTypeDefinition::U64 { pack_length } => Value::U64(b.get_uint_le(*pack_length)),
-        ColumnTypeDefinition::I64 { pack_length } => Value::I64(b.get_int_le(*pack_length)),
-        ColumnTypeDefinition::F64 { pack_length } => match *pack_length {
-          4 => Value::F64(b.get_f32_le().into()),
-          8 => Value::F64(b.get_f64_le()),
-          _ => unreachable!(),
-        },
-        ColumnTypeDefinition::Decimal { precision: _, scale: _ } => {
-          let len = b.mysql_get_lenc_uint().try_into().unwrap();
-          let buffer = b.copy_to_bytes(len);
-          Value::Decimal(buffer)
+      let value = match column_type_definition {
+        ColumnTypeDefinition::U64 { pack_length } => Value::U64(b.mysql_get_uint_le(*pack_length)?),
+        ColumnTypeDefinition::I64 { pack_length } => Value::I64(b.mysql_get_int_le(*pack_length)?),
+        ColumnTypeDefinition::F64 { pack_length: 4 } => Value::F64(b.mysql_get_f32_le()?.into()),
+        ColumnTypeDefinition::F64 { pack_length: 8 } => Value::F64(b.mysql_get_f64_le()?),
+        ColumnTypeDefinition::F64 { pack_length } => {
+          return Err(ProtocolError::invalid(format!("invalid floating point size {}", pack_length)).into())
         }
+        ColumnTypeDefinition::Decimal { precision, scale } => Value::Decimal(Decimal::parse(b, *precision, *scale)?),
         ColumnTypeDefinition::String { pack_length } => {
-          let len = b.get_uint_le(*pack_length).try_into().unwrap();
-          let buffer = b.copy_to_bytes(len);
-          Value::String(String::from_utf8(buffer.into()).unwrap())
+          let len = b.mysql_get_uint_le(*pack_length)? as usize;
+          Value::String(b.mysql_get_fixed_length_string(len)?)
         }
         ColumnTypeDefinition::Blob { pack_length } => {
-          let len = b.get_uint_le(*pack_length).try_into().unwrap();
-          let buffer = b.copy_to_bytes(len);
+          let len = b.mysql_get_uint_le(*pack_length)? as usize;
+          let buffer = b.mysql_get_bytes(len)?;
           Value::Blob(buffer)
         }
         ColumnTypeDefinition::Json { pack_length } => {
-          let len = b.get_uint_le(*pack_length).try_into().unwrap();
-          let buffer = b.copy_to_bytes(len);
+          let len = b.mysql_get_uint_le(*pack_length)? as usize;
+          let buffer = b.mysql_get_bytes(len)?;
           Value::Json(buffer)
         }
         ColumnTypeDefinition::Year => {
-          let year: u64 = b.get_u8().into();
+          let year: u64 = b.mysql_get_u8()?.into();
           Value::U64(1900 + year)
         }
-        ColumnTypeDefinition::Timestamp => Value::U64(b.get_u32_le().into()),
+        ColumnTypeDefinition::Timestamp => Value::U64(b.mysql_get_u32_le()?.into()),
         ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U24) => {
-          let tmp = b.get_uint_le(3);
-          let day = (tmp & 31).try_into().unwrap();
-          let month = ((tmp >> 5) & 15).try_into().unwrap();
-          let year = (tmp >> 9).try_into().unwrap();
+          let tmp = b.mysql_get_uint_le(3)?;
+          let day = narrow(tmp & 31)?;
+          let month = narrow((tmp >> 5) & 15)?;
+          let year = narrow(tmp >> 9)?;
           Value::Date {
             year,
             month,
@@ -658,15 +788,15 @@ fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<
           }
         }
         ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::U64) => {
-          let tmp = b.get_u64_le();
+          let tmp = b.mysql_get_u64_le()?;
           let date = tmp / 1_000_000;
           let time = tmp % 1_000_000;
-          let year = (date / 10000).try_into().unwrap();
-          let month = ((date % 10000) / 100).try_into().unwrap();
-          let day = (date % 100).try_into().unwrap();
-          let hour = (time / 10000).try_into().unwrap();
-          let minute = ((time % 10000) / 100).try_into().unwrap();
-          let second = (time % 100).try_into().unwrap();
+          let year = narrow(date / 10000)?;
+          let month = narrow((date % 10000) / 100)?;
+          let day = narrow(date % 100)?;
+          let hour = narrow(time / 10000)?;
+          let minute = narrow((time % 10000) / 100)?;
+          let second = narrow(time % 100)?;
           Value::Date {
             year,
             month,
@@ -677,12 +807,11 @@ fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<
             micro_second: 0,
           }
         }
-        ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::Arbitrary(_)) => todo!(),
         ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::U24) => {
-          let tmp = b.get_uint_le(3);
-          let hours = (tmp / 10000).try_into().unwrap();
-          let minutes = ((tmp % 10000) / 100).try_into().unwrap();
-          let seconds = (tmp % 100).try_into().unwrap();
+          let tmp = b.mysql_get_uint_le(3)?;
+          let hours = narrow(tmp / 10000)?;
+          let minutes = narrow((tmp % 10000) / 100)?;
+          let seconds = narrow(tmp % 100)?;
           Value::Time {
             hours,
             minutes,
@@ -690,10 +819,12 @@ fn parse_row(b: &mut Bytes, columns: &[Column], _column_present: &Bytes) -> Vec<
             micro_seconds: 0,
           }
         }
-        ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::Arbitrary(_)) => todo!(),
-        ColumnTypeDefinition::Set => todo!(),
-        ColumnTypeDefinition::Enum => todo!(),
-      }
+        ColumnTypeDefinition::Date(ColumnTypeDefinitionDate::Arbitrary(_))
+        | ColumnTypeDefinition::Time(ColumnTypeDefinitionTime::Arbitrary(_))
+        | ColumnTypeDefinition::Set
+        | ColumnTypeDefinition::Enum => return Err(unsupported(column_type_definition)),
+      };
+      Ok(value)
     })
     .collect()
 }
@@ -717,10 +848,10 @@ impl UpdateRowEvent {
       flags,
       extras,
       column_count,
-    } = RowEventHeader::parse(&mut b, use_extras);
-    let bitmap_len = (column_count + 7) / 8;
-    let columns_after_image = b.split_to(bitmap_len);
-    let columns_before_image = b.split_to(bitmap_len);
+    } = RowEventHeader::parse(&mut b, use_extras)?;
+    let bitmap_len = column_count.div_ceil(8);
+    let columns_after_image = b.mysql_get_bytes(bitmap_len)?;
+    let columns_before_image = b.mysql_get_bytes(bitmap_len)?;
     let rows = b;
     Ok(Self {
       table_id,
@@ -733,17 +864,17 @@ impl UpdateRowEvent {
     })
   }
 
-  pub fn rows(&self, columns: &[Column]) -> (Vec<Value>, Vec<Value>) {
+  pub fn rows(&self, columns: &[Column]) -> io::Result<(Vec<Value>, Vec<Value>)> {
     let mut before = vec![];
     let mut after = vec![];
 
     let mut b = self.rows.clone();
     while b.remaining() > 0 {
-      before.append(&mut parse_row(&mut b, columns, &self.columns_before_image));
-      after.append(&mut parse_row(&mut b, columns, &self.columns_after_image));
+      before.append(&mut parse_row(&mut b, columns, &self.columns_before_image)?);
+      after.append(&mut parse_row(&mut b, columns, &self.columns_after_image)?);
     }
 
-    (before, after)
+    Ok((before, after))
   }
 }
 
@@ -765,9 +896,9 @@ impl DeleteRowEvent {
       flags,
       extras,
       column_count,
-    } = RowEventHeader::parse(&mut b, use_extras);
-    let bitmap_len = (column_count + 7) / 8;
-    let columns_before_image = b.split_to(bitmap_len);
+    } = RowEventHeader::parse(&mut b, use_extras)?;
+    let bitmap_len = column_count.div_ceil(8);
+    let columns_before_image = b.mysql_get_bytes(bitmap_len)?;
     let rows = b;
     Ok(Self {
       table_id,
@@ -779,26 +910,91 @@ impl DeleteRowEvent {
     })
   }
 
-  pub fn rows(&self, columns: &[Column]) -> Vec<Value> {
+  pub fn rows(&self, columns: &[Column]) -> io::Result<Vec<Value>> {
     parse_row(&mut self.rows.clone(), columns, &self.columns_before_image)
   }
 }
 
+// First event of a transaction.
 #[derive(Debug)]
-pub struct GtidEvent {}
+pub struct GtidEvent {
+  // 0x01 when the transaction may contain statement based events.
+  pub flags: u8,
+  pub gtid: Gtid,
+  // Logical clock of the transaction: it can be applied in parallel with the transactions whose `sequence_number` is
+  // greater than its `last_committed`. Both are 0 when the source does not record them.
+  pub last_committed: i64,
+  pub sequence_number: i64,
+  // Commit time on the immediate source and on the original source, in microseconds since the epoch.
+  pub immediate_commit_timestamp: Option<u64>,
+  pub original_commit_timestamp: Option<u64>,
+  // Size of the transaction in the binlog, including this event.
+  pub transaction_length: Option<u64>,
+}
 
 impl GtidEvent {
-  pub fn parse(_b: Bytes) -> io::Result<Self> {
-    Ok(GtidEvent {})
+  pub fn parse(mut b: Bytes) -> io::Result<Self> {
+    // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Gtid__event.html
+    const LOGICAL_TIMESTAMP_TYPECODE: u8 = 2;
+    const COMMIT_TIMESTAMP_LEN: usize = 7;
+    const ORIGINAL_COMMIT_TIMESTAMP_FLAG: u64 = 1 << 55;
+
+    let flags = b.mysql_get_u8()?;
+    let source_id = Uuid::from_bytes(b.mysql_get_array()?);
+    let transaction_id = b.mysql_get_u64_le()?;
+    let gtid = Gtid {
+      source_id,
+      transaction_id,
+    };
+
+    let mut last_committed = 0;
+    let mut sequence_number = 0;
+    if b.has_remaining() && b.mysql_get_u8()? == LOGICAL_TIMESTAMP_TYPECODE {
+      last_committed = b.mysql_get_int_le(8)?;
+      sequence_number = b.mysql_get_int_le(8)?;
+    }
+
+    let mut immediate_commit_timestamp = None;
+    let mut original_commit_timestamp = None;
+    if b.has_remaining() {
+      let timestamp = b.mysql_get_uint_le(COMMIT_TIMESTAMP_LEN)?;
+      let immediate = timestamp & !ORIGINAL_COMMIT_TIMESTAMP_FLAG;
+      immediate_commit_timestamp = Some(immediate);
+      // The original commit timestamp is only written when it differs from the immediate one.
+      original_commit_timestamp = match timestamp & ORIGINAL_COMMIT_TIMESTAMP_FLAG {
+        0 => Some(immediate),
+        _ => Some(b.mysql_get_uint_le(COMMIT_TIMESTAMP_LEN)?),
+      };
+    }
+
+    let transaction_length = if b.has_remaining() {
+      Some(b.mysql_get_lenc_uint()?)
+    } else {
+      None
+    };
+
+    Ok(GtidEvent {
+      flags,
+      gtid,
+      last_committed,
+      sequence_number,
+      immediate_commit_timestamp,
+      original_commit_timestamp,
+      transaction_length,
+    })
   }
 }
 
+// GTIDs of the transactions in the previous binlog files, at the start of each binlog file.
 #[derive(Debug)]
-pub struct PreviousGtidEvent {}
+pub struct PreviousGtidEvent {
+  pub gtid_set: GtidSet,
+}
 
 impl PreviousGtidEvent {
-  pub fn parse(_b: Bytes) -> io::Result<Self> {
-    Ok(PreviousGtidEvent {})
+  pub fn parse(b: Bytes) -> io::Result<Self> {
+    let gtid_set = GtidSet::parse(b)?;
+    Ok(PreviousGtidEvent { gtid_set })
   }
 }
 
@@ -809,30 +1005,18 @@ pub struct XidEvent {
 
 impl XidEvent {
   pub fn parse(mut b: Bytes) -> io::Result<Self> {
-    let xid = b.get_u64_le();
+    let xid = b.mysql_get_u64_le()?;
     Ok(Self { xid })
   }
 }
 
-#[derive(Debug)]
-pub struct AnonymousGtidEvent {
-  b: Bytes,
-}
-
-impl AnonymousGtidEvent {
-  pub fn parse(b: Bytes) -> io::Result<Self> {
-    Ok(Self { b })
-  }
-}
-
 #[derive(Debug)]
 pub enum Value {
   Null,
   U64(u64),
   I64(i64),
   F64(f64),
-  // TODO: Parse decimals instead of keeping raw binary data.
-  Decimal(Bytes),
+  Decimal(Decimal),
   String(String),
   Blob(Bytes),
   // TODO: Parse json values, but MYSQL has a custom JSONB protocol because why not.
@@ -856,6 +1040,94 @@ pub enum Value {
   Set,
 }
 
+// Exact value of a DECIMAL/NUMERIC column, e.g. `-1234.5600` for a DECIMAL(8, 4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimal {
+  pub negative: bool,
+  // Digits before the decimal point, without leading zeros, e.g. `1234` or `0`.
+  pub integer: String,
+  // Digits after the decimal point, as many as the scale of the column, e.g. `5600`.
+  pub fraction: String,
+}
+
+impl Decimal {
+  // NEWDECIMAL values are stored as big endian groups of 9 digits in 4 bytes, and the leading integer and trailing
+  // fractional digits that do not fill a group in 1 to 4 bytes. The sign is the inverted most significant bit, and
+  // all the bits of negative values are inverted.
+  //
+  // https://github.com/mysql/mysql-server/blob/8.0/strings/decimal.cc (decimal2bin)
+  fn parse(b: &mut Bytes, precision: u8, scale: u8) -> io::Result<Self> {
+    const DIGITS_PER_GROUP: usize = 9;
+    const GROUP_LEN: usize = 4;
+    const DIGITS_LEN: [usize; DIGITS_PER_GROUP + 1] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+
+    let integer_digits = usize::from(precision.saturating_sub(scale));
+    let fraction_digits = usize::from(scale);
+    let (integer_groups, integer_partial) = (integer_digits / DIGITS_PER_GROUP, integer_digits % DIGITS_PER_GROUP);
+    let (fraction_groups, fraction_partial) = (fraction_digits / DIGITS_PER_GROUP, fraction_digits % DIGITS_PER_GROUP);
+    let len = DIGITS_LEN[integer_partial]
+      + integer_groups * GROUP_LEN
+      + fraction_groups * GROUP_LEN
+      + DIGITS_LEN[fraction_partial];
+
+    let mut buffer = b.mysql_get_bytes(len)?.to_vec();
+    let negative = buffer.first().is_some_and(|v| v & 0x80 == 0);
+    if let Some(v) = buffer.first_mut() {
+      *v ^= 0x80;
+    }
+    if negative {
+      buffer.iter_mut().for_each(|v| *v = !*v);
+    }
+
+    let mut buffer = &buffer[..];
+    let mut read_group = |digits: usize| -> io::Result<String> {
+      if digits == 0 {
+        return Ok(String::new());
+      }
+      let group = buffer.get_uint(DIGITS_LEN[digits]);
+      if group >= 10u64.pow(digits as u32) {
+        return Err(ProtocolError::invalid(format!("invalid decimal digits {}", group)).into());
+      }
+      Ok(format!("{:0width$}", group, width = digits))
+    };
+
+    let mut integer = String::with_capacity(integer_digits);
+    let mut fraction = String::with_capacity(fraction_digits);
+    integer.push_str(&read_group(integer_partial)?);
+    for _ in 0..integer_groups {
+      integer.push_str(&read_group(DIGITS_PER_GROUP)?);
+    }
+    for _ in 0..fraction_groups {
+      fraction.push_str(&read_group(DIGITS_PER_GROUP)?);
+    }
+    fraction.push_str(&read_group(fraction_partial)?);
+
+    let integer = match integer.trim_start_matches('0') {
+      "" => "0".to_string(),
+      integer => integer.to_string(),
+    };
+
+    Ok(Self {
+      negative,
+      integer,
+      fraction,
+    })
+  }
+}
+
+impl fmt::Display for Decimal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.negative {
+      write!(f, "-")?;
+    }
+    write!(f, "{}", self.integer)?;
+    if !self.fraction.is_empty() {
+      write!(f, ".{}", self.fraction)?;
+    }
+    Ok(())
+  }
+}
+
 #[derive(Debug)]
 pub struct Column {
   pub column_name: String,
@@ -895,7 +1167,13 @@ pub enum ColumnTypeDefinition {
 
 #[cfg(test)]
 mod test {
-  use super::{BinlogEvent, BinlogEventHeader, BinlogEventType};
+  use bytes::Bytes;
+  use uuid::Uuid;
+
+  use super::{
+    BinlogChecksum, BinlogEvent, BinlogEventHeader, BinlogEventType, Column, ColumnTypeDefinition, Decimal,
+    ProtocolError, TableMapEvent, XidEvent,
+  };
 
   #[test]
   fn parses_rotate() {
@@ -903,7 +1181,7 @@ mod test {
                                        \x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\
                                        \x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
 
-    let (_header, event) = BinlogEventHeader::parse(ROTATE_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(ROTATE_EVENT.into(), BinlogChecksum::None).unwrap();
     match event {
       BinlogEvent::Rotate(packet) => {
         assert_eq!(150, packet.next_log_position);
@@ -914,7 +1192,6 @@ mod test {
   }
 
   #[test]
-  #[ignore = "broken"]
   fn parses_format_description() {
     const FORMAT_DESCRIPTION_EVENT: &[u8] = b"\x00\xf2\x43\x5d\x5d\x0f\x01\x00\x00\x00\x77\x00\x00\x00\x00\x00\x00\
                                                    \x00\x00\x00\x04\x00\x35\x2e\x37\x2e\x31\x38\x2d\x31\x36\x2d\x6c\x6f\
@@ -925,12 +1202,13 @@ mod test {
                                                    \x02\x00\x00\x00\x0a\x0a\x0a\x2a\x2a\x00\x12\x34\x00\x00\xc2\x36\x0c\
                                                    \xdf";
 
-    let (_header, event) = BinlogEventHeader::parse(FORMAT_DESCRIPTION_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(FORMAT_DESCRIPTION_EVENT.into(), BinlogChecksum::None).unwrap();
     match event {
       BinlogEvent::FormatDescription(packet) => {
         assert_eq!(4, packet.version);
         assert_eq!("5.7.18-16-log", packet.server_version);
         assert_eq!(0, packet.create_timestamp);
+        assert_eq!(BinlogChecksum::None, packet.checksum);
       }
       unexpected => panic!("unexpected {:?}", unexpected),
     }
@@ -938,15 +1216,64 @@ mod test {
 
   #[test]
   fn parses_anonymous_gtid() {
-    const ANONYMOUS_GTID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\x3d\x00\x00\x00\xd3\x00\x00\
+    const ANONYMOUS_GTID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x22\x01\x00\x00\x00\x41\x00\x00\x00\xd3\x00\x00\
                                                \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
                                                \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00\x00\x00\x00\x00\
-                                               \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00";
+                                               \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\xf6\x01\x09\x80";
 
-    let (_header, event) = BinlogEventHeader::parse(ANONYMOUS_GTID_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(ANONYMOUS_GTID_EVENT.into(), BinlogChecksum::Crc32).unwrap();
     match event {
-      BinlogEvent::NotSupported(BinlogEventType::ANONYMOUS_GTID_EVENT) => {}
-      _ => panic!(),
+      BinlogEvent::AnonymousGtid(event) => {
+        assert_eq!(1, event.flags);
+        assert_eq!(Uuid::nil(), event.gtid.source_id);
+        assert_eq!(0, event.gtid.transaction_id);
+        assert_eq!(0, event.last_committed);
+        assert_eq!(1, event.sequence_number);
+        assert_eq!(None, event.immediate_commit_timestamp);
+        assert_eq!(None, event.transaction_length);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_gtid() {
+    const GTID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x21\x01\x00\x00\x00\x52\x00\x00\x00\x00\x02\x00\
+                                     \x00\x00\x00\x00\x3e\x11\xfa\x47\x71\xca\x11\xe1\x9e\x33\xc8\x0a\xa9\
+                                     \x42\x95\x62\x2a\x00\x00\x00\x00\x00\x00\x00\x02\x29\x00\x00\x00\x00\
+                                     \x00\x00\x00\x2a\x00\x00\x00\x00\x00\x00\x00\x00\x40\x1e\x18\x24\x0a\
+                                     \x86\x00\xa0\xab\xc9\x0b\x01\x06\xfc\x2c\x01\x06\x3d\x56\x6f";
+
+    let (_header, event) = BinlogEventHeader::parse(GTID_EVENT.into(), BinlogChecksum::Crc32).unwrap();
+    match event {
+      BinlogEvent::Gtid(event) => {
+        assert_eq!("3e11fa47-71ca-11e1-9e33-c80aa9429562:42", event.gtid.to_string());
+        assert_eq!(0, event.flags);
+        assert_eq!(41, event.last_committed);
+        assert_eq!(42, event.sequence_number);
+        assert_eq!(Some(1700000000000000), event.immediate_commit_timestamp);
+        assert_eq!(Some(1690000000000000), event.original_commit_timestamp);
+        assert_eq!(Some(300), event.transaction_length);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_previous_gtids() {
+    const PREVIOUS_GTIDS_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x23\x01\x00\x00\x00\x57\x00\x00\x00\x00\x01\x00\
+                                               \x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00\x3e\x11\xfa\x47\x71\xca\
+                                               \x11\xe1\x9e\x33\xc8\x0a\xa9\x42\x95\x62\x02\x00\x00\x00\x00\x00\x00\
+                                               \x00\x01\x00\x00\x00\x00\x00\x00\x00\x06\x00\x00\x00\x00\x00\x00\x00\
+                                               \x07\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\xeb\
+                                               \x41\x73\x83";
+
+    let (_header, event) = BinlogEventHeader::parse(PREVIOUS_GTIDS_EVENT.into(), BinlogChecksum::Crc32).unwrap();
+    match event {
+      BinlogEvent::PreviousGtid(event) => {
+        assert_eq!("3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7", event.gtid_set.to_string());
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
     }
   }
 
@@ -958,7 +1285,7 @@ mod test {
                                       \x64\x04\x21\x00\x21\x00\x2d\x00\x70\x65\x74\x73\x00\x42\x45\x47\x49\
                                       \x4e";
 
-    let (_header, event) = BinlogEventHeader::parse(QUERY_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(QUERY_EVENT.into(), BinlogChecksum::None).unwrap();
     match event {
       BinlogEvent::NotSupported(BinlogEventType::QUERY_EVENT) => {}
       _ => panic!(),
@@ -972,7 +1299,7 @@ mod test {
                                           \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\
                                           \x04\x63\x61\x74\x73\x00\x04\x03\x0f\x0f\x0a\x04\x58\x02\x58\x02\x00";
 
-    let (_header, event) = BinlogEventHeader::parse(TABLE_MAP_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(TABLE_MAP_EVENT.into(), BinlogChecksum::None).unwrap();
     match event {
       BinlogEvent::TableMap(packet) => {
         assert_eq!(2605, packet.table_id);
@@ -986,6 +1313,17 @@ mod test {
     }
   }
 
+  #[test]
+  fn parses_table_map_temporal_columns() {
+    // DATETIME2, TIMESTAMP2 and TIME2 columns carry their fractional seconds precision as 1 byte of metadata.
+    const TABLE_MAP_EVENT: &[u8] = b"\x2d\x0a\x00\x00\x00\x00\x01\x00\x04\x70\x65\x74\x73\x00\x04\x63\x61\x74\
+                                          \x73\x00\x03\x12\x11\x13\x03\x06\x00\x03\x07";
+
+    let packet = TableMapEvent::parse(Bytes::from_static(TABLE_MAP_EVENT)).unwrap();
+    assert_eq!(3, packet.column_count);
+    assert_eq!(vec![6, 0, 3], packet.column_metas);
+  }
+
   #[test]
   fn parses_insert_row() {
     const INSERT_ROW_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
@@ -993,7 +1331,7 @@ mod test {
                                            \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
                                            \x65\x72\xb5\xc0\x0f";
 
-    let (_header, event) = BinlogEventHeader::parse(INSERT_ROW_EVENT.into()).unwrap();
+    let (_header, event) = BinlogEventHeader::parse(INSERT_ROW_EVENT.into(), BinlogChecksum::None).unwrap();
     match event {
       BinlogEvent::Insert(packet) => {
         assert_eq!(2605, packet.table_id);
@@ -1003,6 +1341,106 @@ mod test {
     }
   }
 
+  #[test]
+  fn rejects_malformed_events() {
+    const ROTATE_EVENT: &[u8] = b"\x00\x00\x00\x00\x00\x04\x01\x00\x00\x00\x2d\x00\x00\x00\x00\x00\x00\
+                                       \x00\x20\x00\x96\x00\x00\x00\x00\x00\x00\x00\x73\x68\x6f\x70\x69\x66\
+                                       \x79\x2d\x62\x69\x6e\x2e\x30\x30\x30\x30\x30\x35";
+
+    // The header and the position of the next binlog are required.
+    for len in 0..32 {
+      let err =
+        BinlogEventHeader::parse(Bytes::copy_from_slice(&ROTATE_EVENT[..len]), BinlogChecksum::None).unwrap_err();
+      assert_eq!(Some(&ProtocolError::UnexpectedEof), ProtocolError::from_io_error(&err));
+    }
+
+    let mut event = ROTATE_EVENT.to_vec();
+    event[0] = 0xff;
+    let err = BinlogEventHeader::parse(event.into(), BinlogChecksum::None).unwrap_err();
+    assert_eq!(
+      Some(&ProtocolError::UnexpectedPacket(0xff)),
+      ProtocolError::from_io_error(&err)
+    );
+
+    let mut event = ROTATE_EVENT.to_vec();
+    event[5] = 0xfe;
+    let err = BinlogEventHeader::parse(event.into(), BinlogChecksum::None).unwrap_err();
+    assert_eq!(
+      Some(&ProtocolError::unknown("binlog event type", 0xfe_u8)),
+      ProtocolError::from_io_error(&err)
+    );
+  }
+
+  #[test]
+  fn parses_decimals() {
+    let decimal = |b: &'static [u8], precision: u8, scale: u8| {
+      let mut b = Bytes::from_static(b);
+      let decimal = Decimal::parse(&mut b, precision, scale);
+      assert!(decimal.is_err() || b.is_empty());
+      decimal.map(|v| v.to_string())
+    };
+
+    // Examples of https://dev.mysql.com/doc/refman/8.0/en/precision-math-decimal-characteristics.html
+    assert_eq!(
+      "1234567890.1234",
+      decimal(b"\x81\x0d\xfb\x38\xd2\x04\xd2", 14, 4).unwrap()
+    );
+    assert_eq!(
+      "-1234567890.1234",
+      decimal(b"\x7e\xf2\x04\xc7\x2d\xfb\x2d", 14, 4).unwrap()
+    );
+    assert_eq!("0.00", decimal(b"\x80\x00\x00\x00\x00", 10, 2).unwrap());
+    assert_eq!("-5", decimal(b"\x7f\xff\xfa", 5, 0).unwrap());
+    assert_eq!(
+      "0.500000000000000000000000000000",
+      decimal(b"\x9d\xcd\x65\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00", 30, 30).unwrap()
+    );
+
+    let mut b = Bytes::from_static(b"\x81\x0d\xfb\x38\xd2\x04\xd2");
+    assert_eq!(
+      Decimal {
+        negative: false,
+        integer: "1234567890".to_string(),
+        fraction: "1234".to_string(),
+      },
+      Decimal::parse(&mut b, 14, 4).unwrap()
+    );
+
+    // A group larger than its number of digits, and a value shorter than its precision.
+    let err = decimal(b"\x8a", 1, 0).unwrap_err();
+    assert_eq!(
+      Some(&ProtocolError::invalid("invalid decimal digits 10")),
+      ProtocolError::from_io_error(&err)
+    );
+    let err = decimal(b"\x81\x0d\xfb\x38\xd2\x04", 14, 4).unwrap_err();
+    assert_eq!(Some(&ProtocolError::UnexpectedEof), ProtocolError::from_io_error(&err));
+  }
+
+  #[test]
+  fn rejects_malformed_rows() {
+    const INSERT_ROW_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x1e\x01\x00\x00\x00\x37\x00\x00\x00\x80\x01\x00\
+                                           \x00\x00\x00\x2d\x0a\x00\x00\x00\x00\x01\x00\x02\x00\x04\xff\xf0\x04\
+                                           \x00\x00\x00\x07\x00\x43\x68\x61\x72\x6c\x69\x65\x05\x00\x52\x69\x76\
+                                           \x65\x72\xb5\xc0\x0f";
+
+    let (_header, event) = BinlogEventHeader::parse(INSERT_ROW_EVENT.into(), BinlogChecksum::None).unwrap();
+    let event = match event {
+      BinlogEvent::Insert(event) => event,
+      unexpected => panic!("unexpected {:?}", unexpected),
+    };
+
+    // Lengths read from the row are larger than the event.
+    let columns = (0..4)
+      .map(|i| Column {
+        column_name: format!("c{}", i),
+        is_nullable: false,
+        column_type_definition: ColumnTypeDefinition::Blob { pack_length: 4 },
+      })
+      .collect::<Vec<_>>();
+    let err = event.rows(&columns).unwrap_err();
+    assert_eq!(Some(&ProtocolError::UnexpectedEof), ProtocolError::from_io_error(&err));
+  }
+
   #[test]
   fn parses_delete_row() {
     // TODO
@@ -1013,16 +1451,55 @@ mod test {
     // TODO
   }
 
+  const XID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x9b\x01\x00\
+                                  \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+  const XID_EVENT_CRC32: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1f\x00\x00\x00\x9b\x01\x00\
+                                        \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00\x44\xc2\x67\xbc";
+
   #[test]
   fn parses_xid_event() {
-    const XID_EVENT: &[u8] = b"\x00\xfc\x5a\x5d\x5d\x10\x01\x00\x00\x00\x1b\x00\x00\x00\x9b\x01\x00\
-                                    \x00\x00\x00\x72\x0e\x00\x00\x00\x00\x00\x00";
+    let (_header, event) = BinlogEventHeader::parse(XID_EVENT.into(), BinlogChecksum::None).unwrap();
+    match event {
+      BinlogEvent::Xid(event) => assert_eq!(3698, event.xid),
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn verifies_checksums() {
+    const FORMAT_DESCRIPTION_EVENT: &[u8] = b"\x00\xf2\x43\x5d\x5d\x0f\x01\x00\x00\x00\x53\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x04\x00\x38\x2e\x30\x2e\x33\x36\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+                                                   \x00\x00\x00\x00\x00\x00\x00\x00\x13\x38\x0d\x01\x89\x4b\xd4\x46";
 
-    let (_header, event) = BinlogEventHeader::parse(XID_EVENT.into()).unwrap();
+    let (header, event) = BinlogEventHeader::parse(FORMAT_DESCRIPTION_EVENT.into(), BinlogChecksum::None).unwrap();
+    assert_eq!(Some(0x46d44b89), header.checksum);
     match event {
-      BinlogEvent::NotSupported(BinlogEventType::XID_EVENT) => {}
-      _ => panic!(),
+      BinlogEvent::FormatDescription(event) => {
+        assert_eq!("8.0.36", event.server_version);
+        assert_eq!(BinlogChecksum::Crc32, event.checksum);
+        assert_eq!(&[0x38, 0x0d][..], event.event_type_header_lengths);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
     }
+
+    // A corrupted event, or its checksum.
+    for i in [1, 20, FORMAT_DESCRIPTION_EVENT.len() - 1] {
+      let mut event = FORMAT_DESCRIPTION_EVENT.to_vec();
+      event[i] ^= 0x01;
+      let err = BinlogEventHeader::parse(event.into(), BinlogChecksum::None).unwrap_err();
+      assert!(matches!(
+        ProtocolError::from_io_error(&err),
+        Some(ProtocolError::ChecksumMismatch { .. })
+      ));
+    }
+
+    // An event without checksum does not end with a valid one.
+    assert!(BinlogEventHeader::parse(Bytes::from_static(XID_EVENT), BinlogChecksum::Crc32).is_err());
+    let (header, event) = BinlogEventHeader::parse(Bytes::from_static(XID_EVENT_CRC32), BinlogChecksum::Crc32).unwrap();
+    assert_eq!(Some(0xbc67c244), header.checksum);
+    assert!(matches!(event, BinlogEvent::Xid(XidEvent { xid: 3698 })));
   }
 }
 
@@ -1,44 +1,125 @@
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
+use std::io;
 
+use super::error::ProtocolError;
+
+macro_rules! checked_get {
+  ($($name:ident => $get:ident: $t:ty),+ $(,)?) => {
+    $(
+      fn $name(&mut self) -> io::Result<$t> {
+        self.mysql_ensure_remaining(std::mem::size_of::<$t>())?;
+        Ok(self.$get())
+      }
+    )+
+  };
+}
+
+// Readers of the fields of server packets. They return `ProtocolError`s instead of panicking when a packet is
+// malformed or shorter than expected.
 pub trait BufExt: Buf {
-  fn mysql_get_eof_string(&mut self) -> String {
+  fn mysql_ensure_remaining(&self, len: usize) -> io::Result<()> {
+    if self.remaining() < len {
+      return Err(ProtocolError::UnexpectedEof.into());
+    }
+    Ok(())
+  }
+
+  checked_get! {
+    mysql_get_u8 => get_u8: u8,
+    mysql_get_u16_le => get_u16_le: u16,
+    mysql_get_u32_le => get_u32_le: u32,
+    mysql_get_u64_le => get_u64_le: u64,
+    mysql_get_f32_le => get_f32_le: f32,
+    mysql_get_f64_le => get_f64_le: f64,
+  }
+
+  fn mysql_get_uint_le(&mut self, len: usize) -> io::Result<u64> {
+    if len > 8 {
+      return Err(ProtocolError::invalid(format!("invalid integer length {}", len)).into());
+    }
+    self.mysql_ensure_remaining(len)?;
+    Ok(self.get_uint_le(len))
+  }
+
+  fn mysql_get_int_le(&mut self, len: usize) -> io::Result<i64> {
+    if len == 0 || len > 8 {
+      return Err(ProtocolError::invalid(format!("invalid integer length {}", len)).into());
+    }
+    self.mysql_ensure_remaining(len)?;
+    Ok(self.get_int_le(len))
+  }
+
+  // Reads a byte and converts it into one of the protocol enumerations, e.g. `ColumnType`.
+  fn mysql_get_enum<T: TryFrom<u8, Error = u8>>(&mut self, kind: &'static str) -> io::Result<T> {
+    let value = self.mysql_get_u8()?;
+    T::try_from(value).map_err(|v| ProtocolError::unknown(kind, v).into())
+  }
+
+  fn mysql_get_bytes(&mut self, len: usize) -> io::Result<Bytes> {
+    self.mysql_ensure_remaining(len)?;
+    Ok(self.copy_to_bytes(len))
+  }
+
+  fn mysql_get_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+    self.mysql_ensure_remaining(N)?;
+    let mut array = [0; N];
+    self.copy_to_slice(&mut array);
+    Ok(array)
+  }
+
+  fn mysql_skip(&mut self, len: usize) -> io::Result<()> {
+    self.mysql_ensure_remaining(len)?;
+    self.advance(len);
+    Ok(())
+  }
+
+  fn mysql_get_eof_string(&mut self) -> io::Result<String> {
     self.mysql_get_fixed_length_string(self.remaining())
   }
 
   // Returns a utf-8 encoded string terminated by \0.
-  fn mysql_get_null_terminated_string(&mut self) -> String {
+  fn mysql_get_null_terminated_string(&mut self) -> io::Result<String> {
     match self.chunk().iter().position(|x| *x == 0x00) {
       Some(len) => {
         let mut buffer = vec![0; len];
         self.copy_to_slice(buffer.as_mut_slice());
         self.advance(1);
-        String::from_utf8(buffer).unwrap()
+        String::from_utf8(buffer).map_err(|_| ProtocolError::InvalidUtf8.into())
       }
-      None => panic!("missing null terminator"),
+      None => Err(ProtocolError::MissingNullTerminator.into()),
     }
   }
 
   // Returns a utf-8 encoded string of length N, where N are in bytes.
-  fn mysql_get_fixed_length_string(&mut self, len: usize) -> String {
+  fn mysql_get_fixed_length_string(&mut self, len: usize) -> io::Result<String> {
+    self.mysql_ensure_remaining(len)?;
     let mut bytes = vec![0; len];
     self.copy_to_slice(bytes.as_mut_slice());
-    String::from_utf8(bytes).unwrap()
+    String::from_utf8(bytes).map_err(|_| ProtocolError::InvalidUtf8.into())
   }
 
   // Returns a utf-8 encoded string of variable length. See `BufExt::get_lenc_uint`.
-  fn mysql_get_lenc_string(&mut self) -> String {
-    let len = self.mysql_get_lenc_uint();
-    let len = len.try_into().unwrap();
+  fn mysql_get_lenc_string(&mut self) -> io::Result<String> {
+    let len = self.mysql_get_lenc_len()?;
     self.mysql_get_fixed_length_string(len)
   }
 
-  fn mysql_get_lenc_uint(&mut self) -> u64 {
-    match self.get_u8() {
-      0xfc => self.get_uint_le(2),
-      0xfd => self.get_uint_le(3),
-      0xfe => self.get_uint_le(8),
-      0xff => panic!("Invalid length-encoded integer value",),
-      x => x.into(),
+  fn mysql_get_lenc_uint(&mut self) -> io::Result<u64> {
+    match self.mysql_get_u8()? {
+      0xfc => self.mysql_get_uint_le(2),
+      0xfd => self.mysql_get_uint_le(3),
+      0xfe => self.mysql_get_uint_le(8),
+      0xff => Err(ProtocolError::invalid("Invalid length-encoded integer value").into()),
+      x => Ok(x.into()),
+    }
+  }
+
+  // Reads a length-encoded integer that is the length of the bytes that follow it.
+  fn mysql_get_lenc_len(&mut self) -> io::Result<usize> {
+    let len = self.mysql_get_lenc_uint()?;
+    match usize::try_from(len) {
+      Ok(len) if len <= self.remaining() => Ok(len),
+      _ => Err(ProtocolError::UnexpectedEof.into()),
     }
   }
 }
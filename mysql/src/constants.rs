@@ -2,6 +2,7 @@ use bitflags::bitflags;
 
 pub const MYSQL_NATIVE_PASSWORD_PLUGIN_NAME: &str = "mysql_native_password";
 pub const CACHING_SHA2_PASSWORD_PLUGIN_NAME: &str = "caching_sha2_password";
+pub const MYSQL_CLEAR_PASSWORD_PLUGIN_NAME: &str = "mysql_clear_password";
 pub const MAX_PAYLOAD_LEN: usize = 16777215;
 
 // https://dev.mysql.com/doc/dev/mysql-server/latest/group__group__cs__column__definition__flags.html
@@ -28,6 +29,8 @@ bitflags! {
 bitflags! {
   pub struct BinlogDumpFlags: u16 {
     const NON_BLOCK = 0x0001;
+    const THROUGH_POSITION = 0x0002;
+    const THROUGH_GTID = 0x0004;
   }
 }
 
@@ -61,6 +64,7 @@ bitflags! {
       const CLIENT_CAN_HANDLE_EXPIRED_PASSWORDS = 0x00400000;
       const CLIENT_SESSION_TRACK = 0x00800000;
       const CLIENT_DEPRECATE_EOF = 0x01000000;
+      const CLIENT_MULTI_FACTOR_AUTHENTICATION = 0x10000000;
       const CLIENT_PROGRESS_OBSOLETE = 0x20000000;
       const CLIENT_SSL_VERIFY_SERVER_CERT = 0x40000000;
       const CLIENT_REMEMBER_OPTIONS = 0x80000000;
@@ -0,0 +1,62 @@
+use std::{error, fmt, io};
+
+// Malformed or unexpected packet received from the server. It is returned wrapped in an `io::Error` of kind
+// `InvalidData`, see `ProtocolError::from_io_error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+  // Packet that is not expected at this point of the protocol flow, identified by its header byte.
+  UnexpectedPacket(u8),
+  // The packet ended before all of its fields were read.
+  UnexpectedEof,
+  MissingNullTerminator,
+  InvalidUtf8,
+  // Code of an enumeration (character set, column type, binlog event type, ...) that is not known.
+  UnknownValue { kind: &'static str, value: u64 },
+  // The CRC32 of a binlog event does not match its content.
+  ChecksumMismatch { expected: u32, computed: u32 },
+  // Any other malformed field, e.g. a length that does not match the packet.
+  Invalid(String),
+}
+
+impl ProtocolError {
+  pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+    err.get_ref().and_then(|err| err.downcast_ref::<Self>())
+  }
+
+  pub(crate) fn invalid(msg: impl Into<String>) -> Self {
+    Self::Invalid(msg.into())
+  }
+
+  pub(crate) fn unknown(kind: &'static str, value: impl Into<u64>) -> Self {
+    Self::UnknownValue {
+      kind,
+      value: value.into(),
+    }
+  }
+}
+
+impl fmt::Display for ProtocolError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnexpectedPacket(header) => write!(f, "Unexpected packet: 0x{:02X}", header),
+      Self::UnexpectedEof => write!(f, "packet is shorter than its fields"),
+      Self::MissingNullTerminator => write!(f, "missing null terminator"),
+      Self::InvalidUtf8 => write!(f, "string is not valid utf-8"),
+      Self::UnknownValue { kind, value } => write!(f, "unknown {} {}", kind, value),
+      Self::ChecksumMismatch { expected, computed } => write!(
+        f,
+        "binlog event checksum mismatch: expected 0x{:08X}, computed 0x{:08X}",
+        expected, computed
+      ),
+      Self::Invalid(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+impl error::Error for ProtocolError {}
+
+impl From<ProtocolError> for io::Error {
+  fn from(err: ProtocolError) -> Self {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+  }
+}
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::{fmt, io};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use uuid::Uuid;
+
+use super::buf_ext::BufExt;
+use super::error::ProtocolError;
+
+// Global transaction identifier: the UUID of the server on which the transaction was committed, and the sequence
+// number of the transaction on that server (starting at 1).
+//
+// https://dev.mysql.com/doc/refman/8.0/en/replication-gtids-concepts.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gtid {
+  pub source_id: Uuid,
+  pub transaction_id: u64,
+}
+
+impl fmt::Display for Gtid {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.source_id, self.transaction_id)
+  }
+}
+
+// Set of GTIDs, e.g. `@@GLOBAL.gtid_executed`. Its text form is a comma separated list of `<source_id>:<intervals>`,
+// e.g. `3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5:7,8ca9e4a1-71ca-11e1-9e33-c80aa9429562:1-3`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GtidSet {
+  // Sorted, disjoint and non-adjacent `[start, end)` intervals of transaction ids, by source.
+  intervals: BTreeMap<Uuid, Vec<(u64, u64)>>,
+}
+
+impl GtidSet {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.intervals.is_empty()
+  }
+
+  pub fn contains(&self, gtid: &Gtid) -> bool {
+    self.intervals.get(&gtid.source_id).is_some_and(|intervals| {
+      let i = intervals.partition_point(|&(_, end)| end <= gtid.transaction_id);
+      intervals.get(i).is_some_and(|&(start, _)| start <= gtid.transaction_id)
+    })
+  }
+
+  pub fn insert(&mut self, gtid: Gtid) -> io::Result<()> {
+    let end = gtid
+      .transaction_id
+      .checked_add(1)
+      .ok_or_else(|| ProtocolError::invalid(format!("invalid gtid {}", gtid)))?;
+    self.insert_interval(gtid.source_id, gtid.transaction_id, end);
+    Ok(())
+  }
+
+  pub fn union(&self, other: &GtidSet) -> GtidSet {
+    let mut union = self.clone();
+    for (source_id, intervals) in &other.intervals {
+      for &(start, end) in intervals {
+        union.insert_interval(*source_id, start, end);
+      }
+    }
+    union
+  }
+
+  fn insert_interval(&mut self, source_id: Uuid, start: u64, end: u64) {
+    if start >= end {
+      return;
+    }
+
+    // Merges the intervals that overlap or are adjacent to `[start, end)`.
+    let intervals = self.intervals.entry(source_id).or_default();
+    let i = intervals.partition_point(|&(_, e)| e < start);
+    let j = intervals.partition_point(|&(s, _)| s <= end);
+    let merged = intervals[i..j]
+      .iter()
+      .fold((start, end), |(start, end), &(s, e)| (start.min(s), end.max(e)));
+    intervals.splice(i..j, [merged]);
+  }
+
+  // Binary form used by the PREVIOUS_GTIDS_EVENT and COM_BINLOG_DUMP_GTID.
+  //
+  // https://dev.mysql.com/doc/dev/mysql-server/latest/classbinary__log_1_1Previous__gtids__event.html
+  pub(crate) fn parse(mut b: Bytes) -> io::Result<Self> {
+    let mut set = Self::new();
+    let source_count = b.mysql_get_u64_le()?;
+    for _ in 0..source_count {
+      let source_id = Uuid::from_bytes(b.mysql_get_array()?);
+      let interval_count = b.mysql_get_u64_le()?;
+      for _ in 0..interval_count {
+        let start = b.mysql_get_u64_le()?;
+        let end = b.mysql_get_u64_le()?;
+        if start == 0 || start >= end {
+          return Err(ProtocolError::invalid(format!("invalid gtid interval {}-{}", start, end)).into());
+        }
+        set.insert_interval(source_id, start, end);
+      }
+    }
+    Ok(set)
+  }
+
+  pub(crate) fn encode(&self) -> Bytes {
+    let mut b = BytesMut::new();
+    b.put_u64_le(self.intervals.len() as u64);
+    for (source_id, intervals) in &self.intervals {
+      b.put(&source_id.as_bytes()[..]);
+      b.put_u64_le(intervals.len() as u64);
+      for &(start, end) in intervals {
+        b.put_u64_le(start);
+        b.put_u64_le(end);
+      }
+    }
+    b.into()
+  }
+}
+
+impl TryFrom<Gtid> for GtidSet {
+  type Error = io::Error;
+
+  fn try_from(gtid: Gtid) -> Result<Self, Self::Error> {
+    let mut set = Self::new();
+    set.insert(gtid)?;
+    Ok(set)
+  }
+}
+
+impl fmt::Display for GtidSet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, (source_id, intervals)) in self.intervals.iter().enumerate() {
+      if i > 0 {
+        write!(f, ",")?;
+      }
+      write!(f, "{}", source_id)?;
+      for &(start, end) in intervals {
+        match end - start {
+          1 => write!(f, ":{}", start)?,
+          _ => write!(f, ":{}-{}", start, end - 1)?,
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for GtidSet {
+  type Err = io::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid gtid set {}", s));
+
+    let mut set = Self::new();
+    // `@@GLOBAL.gtid_executed` separates the sources with a comma and a new line.
+    for source in s.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+      let mut parts = source.split(':');
+      let source_id = parts
+        .next()
+        .and_then(|v| Uuid::parse_str(v.trim()).ok())
+        .ok_or_else(invalid)?;
+      for interval in parts {
+        let (start, end) = interval.split_once('-').unwrap_or((interval, interval));
+        let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u64>().map_err(|_| invalid())?;
+        if start == 0 || start > end {
+          return Err(invalid());
+        }
+        set.insert_interval(source_id, start, end.checked_add(1).ok_or_else(invalid)?);
+      }
+    }
+    Ok(set)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const SOURCE_1: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+  const SOURCE_2: &str = "8ca9e4a1-71ca-11e1-9e33-c80aa9429562";
+
+  fn gtid(source_id: &str, transaction_id: u64) -> Gtid {
+    Gtid {
+      source_id: source_id.parse().unwrap(),
+      transaction_id,
+    }
+  }
+
+  #[test]
+  fn parses_and_formats_gtid_sets() {
+    let set = format!("{}:1-5:7,\n{}:1-3", SOURCE_2.to_uppercase(), SOURCE_1)
+      .parse::<GtidSet>()
+      .unwrap();
+    assert_eq!(format!("{}:1-3,{}:1-5:7", SOURCE_1, SOURCE_2), set.to_string());
+
+    // Overlapping and adjacent intervals are merged.
+    let set = format!("{}:4-6:1-3:9:8", SOURCE_1).parse::<GtidSet>().unwrap();
+    assert_eq!(format!("{}:1-6:8-9", SOURCE_1), set.to_string());
+
+    assert!("".parse::<GtidSet>().unwrap().is_empty());
+    assert!(format!("{}:0", SOURCE_1).parse::<GtidSet>().is_err());
+    assert!(format!("{}:5-3", SOURCE_1).parse::<GtidSet>().is_err());
+    assert!(format!("{}:a", SOURCE_1).parse::<GtidSet>().is_err());
+    assert!(format!("{}:1-18446744073709551615", SOURCE_1)
+      .parse::<GtidSet>()
+      .is_err());
+    assert!("3e11fa47:1".parse::<GtidSet>().is_err());
+  }
+
+  #[test]
+  fn contains_and_unions_gtids() {
+    let mut set = format!("{}:1-5:7", SOURCE_1).parse::<GtidSet>().unwrap();
+    assert!(set.contains(&gtid(SOURCE_1, 1)));
+    assert!(set.contains(&gtid(SOURCE_1, 5)));
+    assert!(!set.contains(&gtid(SOURCE_1, 6)));
+    assert!(set.contains(&gtid(SOURCE_1, 7)));
+    assert!(!set.contains(&gtid(SOURCE_1, 8)));
+    assert!(!set.contains(&gtid(SOURCE_2, 1)));
+
+    set.insert(gtid(SOURCE_1, 6)).unwrap();
+    assert_eq!(format!("{}:1-7", SOURCE_1), set.to_string());
+    assert!(set.insert(gtid(SOURCE_1, u64::MAX)).is_err());
+
+    let other = format!("{}:9,{}:1-2", SOURCE_1, SOURCE_2).parse::<GtidSet>().unwrap();
+    let union = set.union(&other);
+    assert_eq!(format!("{}:1-7:9,{}:1-2", SOURCE_1, SOURCE_2), union.to_string());
+    assert_eq!(union, other.union(&set));
+    assert!(union.contains(&gtid(SOURCE_2, 2)));
+  }
+
+  #[test]
+  fn encodes_gtid_sets() {
+    let set = format!("{}:1-5:7,{}:1-3", SOURCE_1, SOURCE_2)
+      .parse::<GtidSet>()
+      .unwrap();
+    let b = set.encode();
+    assert_eq!(8 + 2 * (16 + 8) + 3 * 16, b.len());
+    assert_eq!(set, GtidSet::parse(b.clone()).unwrap());
+
+    let err = GtidSet::parse(b.slice(..b.len() - 1)).unwrap_err();
+    assert_eq!(Some(&ProtocolError::UnexpectedEof), ProtocolError::from_io_error(&err));
+  }
+}
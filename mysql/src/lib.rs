@@ -4,11 +4,16 @@ mod buf_ext;
 mod conn;
 mod constants;
 mod debug;
+pub mod error;
+mod gtid;
+mod pool;
 mod query;
 mod scramble;
 mod stream;
 
-pub use conn::{BinlogCursor, BinlogStream, Connection, ConnectionOptions};
+pub use conn::{BinlogCursor, BinlogStream, Connection, ConnectionOptions, SslMode};
+pub use gtid::{Gtid, GtidSet};
+pub use pool::{Manageable, Pool, PoolOptions, PoolStatus, PooledConnection};
 
 #[cfg(feature = "ssl")]
 pub use openssl;
@@ -0,0 +1,33 @@
+use std::io;
+
+use url::Url;
+
+use super::conn::Connection;
+
+pub use pool::{Manageable, PoolOptions, PoolStatus};
+
+pub type Pool = pool::Pool<Connection>;
+pub type PooledConnection = pool::PooledConnection<Connection>;
+
+impl Manageable for Connection {
+  async fn connect(url: &Url) -> io::Result<Self> {
+    Connection::connect_from_url(url).await
+  }
+
+  #[cfg(feature = "ssl")]
+  async fn connect_ssl(url: &Url, ssl_connector: openssl::ssl::SslConnector) -> io::Result<Self> {
+    Connection::connect_ssl_from_url(url, ssl_connector).await
+  }
+
+  async fn ping(&mut self) -> io::Result<()> {
+    Connection::ping(self).await
+  }
+
+  async fn close(self) -> io::Result<()> {
+    Connection::close(self).await
+  }
+
+  fn is_reusable(&self) -> bool {
+    Connection::is_reusable(self)
+  }
+}
@@ -3,11 +3,12 @@ use std::{
   slice::{ChunksExact, ChunksExactMut},
 };
 
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 
 use super::{
   buf_ext::BufExt,
   constants::{CharacterSet, ColumnFlags, ColumnType},
+  error::ProtocolError,
 };
 
 /// Owned results for 0..N rows.
@@ -81,20 +82,26 @@ pub struct Column {
 
 impl Column {
   pub(crate) fn parse(mut b: Bytes) -> io::Result<Self> {
-    let catalog = b.mysql_get_lenc_string();
-    assert_eq!("def", catalog.as_str());
-    let schema = b.mysql_get_lenc_string();
-    let table = b.mysql_get_lenc_string();
-    let org_table = b.mysql_get_lenc_string();
-    let name = b.mysql_get_lenc_string();
-    let _org_name = b.mysql_get_lenc_string();
-    let fixed_len = b.mysql_get_lenc_uint();
-    assert_eq!(0x0C, fixed_len);
-    let character_set = (b.get_u16_le() as u8).try_into().unwrap();
-    let column_length = b.get_u32_le();
-    let column_type = b.get_u8().try_into().unwrap();
-    let flags = ColumnFlags::from_bits_truncate(b.get_u16_le());
-    let decimals = b.get_u8();
+    let catalog = b.mysql_get_lenc_string()?;
+    if catalog != "def" {
+      return Err(ProtocolError::invalid(format!("unexpected column catalog {:?}", catalog)).into());
+    }
+    let schema = b.mysql_get_lenc_string()?;
+    let table = b.mysql_get_lenc_string()?;
+    let org_table = b.mysql_get_lenc_string()?;
+    let name = b.mysql_get_lenc_string()?;
+    let _org_name = b.mysql_get_lenc_string()?;
+    let fixed_len = b.mysql_get_lenc_uint()?;
+    if fixed_len != 0x0C {
+      return Err(ProtocolError::invalid(format!("unexpected column fields length {}", fixed_len)).into());
+    }
+    let character_set = b.mysql_get_u16_le()?;
+    let character_set = CharacterSet::try_from(character_set as u8)
+      .map_err(|_| ProtocolError::unknown("character set", character_set))?;
+    let column_length = b.mysql_get_u32_le()?;
+    let column_type = b.mysql_get_enum("column type")?;
+    let flags = ColumnFlags::from_bits_truncate(b.mysql_get_u16_le()?);
+    let decimals = b.mysql_get_u8()?;
 
     Ok(Self {
       catalog,
@@ -47,7 +47,7 @@ pub fn scramble_native(nonce: &[u8], password: &[u8]) -> [u8; 20] {
   xor(sha1_1(password), sha1_2(nonce, sha1_1(sha1_1(password))))
 }
 
-/// Scramble algorithm used in cached_sha2_password fast path.
+/// Scramble algorithm used in caching_sha2_password fast path.
 ///
 /// XOR(SHA256(password), SHA256(SHA256(SHA256(password)), nonce))
 pub fn scramble_sha256(nonce: &[u8], password: &[u8]) -> [u8; 32] {
@@ -68,6 +68,37 @@ pub fn scramble_sha256(nonce: &[u8], password: &[u8]) -> [u8; 32] {
   xor(sha256_1(password), sha256_2(sha256_1(sha256_1(password)), nonce))
 }
 
+/// Password sent in clear by the caching_sha2_password full authentication, masked with the nonce before it is
+/// encrypted with the server's RSA public key.
+///
+/// XOR(password + '\0', nonce)
+pub fn xor_password_nonce(nonce: &[u8], password: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(password.len() + 1);
+  out.extend_from_slice(password);
+  out.push(0);
+  if !nonce.is_empty() {
+    out.iter_mut().zip(nonce.iter().cycle()).for_each(|(l, r)| *l ^= r);
+  }
+  out
+}
+
+/// Password sent by the caching_sha2_password full authentication over an unencrypted connection.
+///
+/// RSA_OAEP(public_key, XOR(password + '\0', nonce))
+#[cfg(feature = "ssl")]
+pub fn encrypt_password_rsa(
+  nonce: &[u8],
+  password: &[u8],
+  public_key_pem: &[u8],
+) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+  use openssl::rsa::{Padding, Rsa};
+  let rsa = Rsa::public_key_from_pem(public_key_pem)?;
+  let mut out = vec![0; rsa.size() as usize];
+  let len = rsa.public_encrypt(&xor_password_nonce(nonce, password), &mut out, Padding::PKCS1_OAEP)?;
+  out.truncate(len);
+  Ok(out)
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -97,4 +128,34 @@ mod test {
       ]
     );
   }
+
+  #[test]
+  fn xors_password_with_nonce() {
+    let nonce = [0x01, 0x02, 0x03];
+    assert_eq!(vec![0x01], xor_password_nonce(&nonce, b""));
+    assert_eq!(
+      vec![b'p' ^ 0x01, b'a' ^ 0x02, b's' ^ 0x03, b's' ^ 0x01, 0x02],
+      xor_password_nonce(&nonce, b"pass")
+    );
+    assert_eq!(b"pass\0".to_vec(), xor_password_nonce(&[], b"pass"));
+  }
+
+  #[cfg(feature = "ssl")]
+  #[test]
+  fn encrypts_password_rsa() {
+    use openssl::rsa::{Padding, Rsa};
+    let rsa = Rsa::generate(2048).unwrap();
+    let public_key = rsa.public_key_to_pem().unwrap();
+    let nonce = b"0123456789abcdefghij";
+
+    let encrypted = encrypt_password_rsa(nonce, b"password", &public_key).unwrap();
+    assert_eq!(256, encrypted.len());
+
+    let mut decrypted = vec![0; rsa.size() as usize];
+    let len = rsa
+      .private_decrypt(&encrypted, &mut decrypted, Padding::PKCS1_OAEP)
+      .unwrap();
+    assert_eq!(xor_password_nonce(nonce, b"password"), decrypted[..len]);
+    assert!(encrypt_password_rsa(nonce, b"password", b"not a key").is_err());
+  }
 }
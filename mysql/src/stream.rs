@@ -11,7 +11,10 @@ use tokio::{
 };
 
 #[cfg(feature = "ssl")]
-use openssl::ssl::SslConnector;
+use openssl::ssl::{SslConnector, SslVerifyMode};
+
+#[cfg(feature = "ssl")]
+use super::conn::SslMode;
 
 #[cfg(feature = "ssl")]
 use tokio_openssl::SslStream;
@@ -20,7 +23,7 @@ use tokio_openssl::SslStream;
 pub enum Stream {
   Tcp((BufStream<TcpStream>, Vec<SocketAddr>)),
   #[cfg(feature = "ssl")]
-  Ssl(SslStream<BufStream<TcpStream>>),
+  Ssl((SslStream<BufStream<TcpStream>>, Vec<SocketAddr>)),
 }
 
 impl Stream {
@@ -33,23 +36,40 @@ impl Stream {
   pub async fn duplicate(&self) -> io::Result<Self> {
     match self {
       Stream::Tcp((_, addrs)) => Self::connect_tcp(addrs.clone()).await,
+      // TLS is negotiated again during the handshake.
       #[cfg(feature = "ssl")]
-      Stream::Ssl(_) => todo!(),
+      Stream::Ssl((_, addrs)) => Self::connect_tcp(addrs.clone()).await,
     }
   }
 
-  #[cfg(feature = "ssl")]
-  async fn into_ssl(self, domain: impl Into<String>, ssl_connector: SslConnector) -> io::Result<Self> {
+  pub fn is_ssl(&self) -> bool {
     match self {
-      Stream::Tcp((s, _addrs)) => {
-        let domain = domain.into();
+      Stream::Tcp(_) => false,
+      #[cfg(feature = "ssl")]
+      Stream::Ssl(_) => true,
+    }
+  }
 
-        let connect_configuration = ssl_connector
+  // Negotiates TLS once the SSLRequest is sent, and verifies the server certificate according to the mode.
+  #[cfg(feature = "ssl")]
+  pub(crate) async fn into_ssl(self, domain: &str, ssl_connector: SslConnector, ssl_mode: SslMode) -> io::Result<Self> {
+    match self {
+      Stream::Tcp((s, addrs)) => {
+        let mut connect_configuration = ssl_connector
           .configure()
           .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to create SSL configuration"))?;
 
+        match ssl_mode {
+          SslMode::Disabled | SslMode::Preferred | SslMode::Required => {
+            connect_configuration.set_verify(SslVerifyMode::NONE);
+            connect_configuration.set_verify_hostname(false);
+          }
+          SslMode::VerifyCa => connect_configuration.set_verify_hostname(false),
+          SslMode::VerifyIdentity => {}
+        }
+
         let ssl = connect_configuration
-          .into_ssl(domain.as_str())
+          .into_ssl(domain)
           .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to create SSL context"))?;
 
         let mut ssl_stream =
@@ -60,7 +80,7 @@ impl Stream {
           .await
           .map_err(|err| io::Error::new(io::ErrorKind::ConnectionRefused, err.to_string()))?;
 
-        Ok(Self::Ssl(ssl_stream))
+        Ok(Self::Ssl((ssl_stream, addrs)))
       }
       s @ Stream::Ssl(_) => Ok(s),
     }
@@ -72,7 +92,7 @@ impl AsyncRead for Stream {
     match self.get_mut() {
       Stream::Tcp((s, _)) => Pin::new(s).poll_read(cx, buf),
       #[cfg(feature = "ssl")]
-      Stream::Ssl(s) => Pin::new(s).poll_read(cx, buf),
+      Stream::Ssl((s, _)) => Pin::new(s).poll_read(cx, buf),
     }
   }
 }
@@ -82,7 +102,7 @@ impl AsyncWrite for Stream {
     match self.get_mut() {
       Stream::Tcp((s, _)) => Pin::new(s).poll_write(cx, buf),
       #[cfg(feature = "ssl")]
-      Stream::Ssl(s) => Pin::new(s).poll_write(cx, buf),
+      Stream::Ssl((s, _)) => Pin::new(s).poll_write(cx, buf),
     }
   }
 
@@ -90,7 +110,7 @@ impl AsyncWrite for Stream {
     match self.get_mut() {
       Stream::Tcp((s, _)) => Pin::new(s).poll_flush(cx),
       #[cfg(feature = "ssl")]
-      Stream::Ssl(s) => Pin::new(s).poll_flush(cx),
+      Stream::Ssl((s, _)) => Pin::new(s).poll_flush(cx),
     }
   }
 
@@ -98,7 +118,7 @@ impl AsyncWrite for Stream {
     match self.get_mut() {
       Stream::Tcp((s, _)) => Pin::new(s).poll_shutdown(cx),
       #[cfg(feature = "ssl")]
-      Stream::Ssl(s) => Pin::new(s).poll_shutdown(cx),
+      Stream::Ssl((s, _)) => Pin::new(s).poll_shutdown(cx),
     }
   }
 }
@@ -1,9 +1,11 @@
+use std::io;
+
 use clap::{value_parser, Arg, Command};
 use url::Url;
 
 use mysql::{
   binlog::{self, TableMapEvent},
-  BinlogCursor,
+  BinlogCursor, Gtid,
 };
 use sink::{Column, ColumnType, ColumnValue, RowEvent};
 
@@ -44,38 +46,63 @@ async fn main() {
   let mut processor = EventProcessor {
     table_map_event: None,
     binlog_cursor,
+    pending_gtid: None,
   };
 
-  loop {
+  // The stream stops on the first error, as skipping the event would lose its rows and the resume set would skip
+  // its transaction on restart.
+  let result = loop {
     tokio::select! {
-        Ok(_) = &mut interrupt => break,
+        Ok(_) = &mut interrupt => break Ok(()),
         event = stream.recv() => {
             match event {
-                Some(Ok((header, event))) => {
-                  if let Some(event) = processor.process_event(header, event) {
-                    println!("{:?}", event);
-                  }
+                Some(Ok((header, event))) => match processor.process_event(header, event) {
+                  Ok(Some(event)) => println!("{:?}", event),
+                  Ok(None) => {},
+                  Err(err) => break Err(format!("binlog event error: {:?}", err)),
                 },
-                Some(Err(err)) => eprintln!("binlog stream error: {:?}", err),
-                None => break,
+                Some(Err(err)) => break Err(format!("binlog stream error: {:?}", err)),
+                None => break Ok(()),
             }
         },
     }
-  }
+  };
 
   stream.close().await.unwrap();
+
+  if let Err(err) = result {
+    eprintln!("{}", err);
+    std::process::exit(1);
+  }
 }
 
 struct EventProcessor {
   binlog_cursor: BinlogCursor,
   table_map_event: Option<TableMapEvent>,
+  pending_gtid: Option<Gtid>,
 }
 
 impl EventProcessor {
-  fn process_event(&mut self, header: binlog::BinlogEventHeader, event: binlog::BinlogEvent) -> Option<RowEvent> {
-    fn map_column_change(table_map_event: &TableMapEvent, row_event: &binlog::InsertRowEvent) -> Vec<Column> {
-      let columns = table_map_event.columns();
-      let values = row_event.rows(&columns);
+  fn commit_gtid(&mut self) -> io::Result<()> {
+    match (self.pending_gtid.take(), self.binlog_cursor.gtid_set.as_mut()) {
+      (Some(gtid), Some(gtid_set)) => gtid_set.insert(gtid),
+      _ => Ok(()),
+    }
+  }
+
+  fn process_event(
+    &mut self,
+    header: binlog::BinlogEventHeader,
+    event: binlog::BinlogEvent,
+  ) -> io::Result<Option<RowEvent>> {
+    fn map_column_change(
+      table_map_event: &TableMapEvent,
+      row_event: &binlog::InsertRowEvent,
+    ) -> io::Result<Vec<Column>> {
+      let columns = table_map_event.columns()?;
+      let values = row_event.rows(&columns)?;
+
+      let unsupported = |what: &str| io::Error::new(io::ErrorKind::Unsupported, format!("{} are not supported", what));
 
       columns
         .into_iter()
@@ -95,50 +122,50 @@ impl EventProcessor {
             binlog::ColumnTypeDefinition::Year => ColumnType::U64,
             binlog::ColumnTypeDefinition::Time(_) => ColumnType::Time,
             binlog::ColumnTypeDefinition::Timestamp => ColumnType::Timestamp,
-            binlog::ColumnTypeDefinition::Enum => todo!(),
-            binlog::ColumnTypeDefinition::Set => todo!(),
+            binlog::ColumnTypeDefinition::Enum => return Err(unsupported("enum columns")),
+            binlog::ColumnTypeDefinition::Set => return Err(unsupported("set columns")),
           };
           let value = match v {
             binlog::Value::Null => ColumnValue::Null,
             binlog::Value::U64(v) => ColumnValue::U64(v),
             binlog::Value::I64(v) => ColumnValue::I64(v),
             binlog::Value::F64(v) => ColumnValue::F64(v),
-            binlog::Value::Decimal(_) => todo!(),
+            binlog::Value::Decimal(v) => ColumnValue::String(v.to_string()),
             binlog::Value::String(v) => ColumnValue::String(v),
             binlog::Value::Blob(v) => ColumnValue::Bytes(v),
-            binlog::Value::Json(_) => todo!(),
-            binlog::Value::Date { .. } => todo!(),
-            binlog::Value::Time { .. } => todo!(),
-            binlog::Value::Enum => todo!(),
-            binlog::Value::Set => todo!(),
+            binlog::Value::Json(_) => return Err(unsupported("json values")),
+            binlog::Value::Date { .. } => return Err(unsupported("date values")),
+            binlog::Value::Time { .. } => return Err(unsupported("time values")),
+            binlog::Value::Enum => return Err(unsupported("enum values")),
+            binlog::Value::Set => return Err(unsupported("set values")),
           };
-          Column {
+          Ok(Column {
             name,
             is_nullable,
             column_type,
             value,
-          }
+          })
         })
-        .collect::<Vec<_>>()
+        .collect()
     }
 
     match event {
       binlog::BinlogEvent::TableMap(v) => {
         self.table_map_event.replace(v);
-        None
+        Ok(None)
       }
 
       binlog::BinlogEvent::Insert(v) => {
         let table_map_event = self.table_map_event.take().unwrap();
-        let columns = map_column_change(&table_map_event, &v);
+        let columns = map_column_change(&table_map_event, &v)?;
         let schema = table_map_event.schema;
         let table = table_map_event.table;
         self.binlog_cursor.log_position = header.log_position;
-        Some(RowEvent::Insert { schema, table, columns })
+        Ok(Some(RowEvent::Insert { schema, table, columns }))
       }
 
       binlog::BinlogEvent::Update(_v) => {
-        None
+        Ok(None)
         // let table_map_event = self.table_map_event.take().unwrap();
         // let columns = vec![];
         // let identity = map_column_change(&table_map_event, &v);
@@ -154,7 +181,7 @@ impl EventProcessor {
       }
 
       binlog::BinlogEvent::Delete(_v) => {
-        None
+        Ok(None)
         // let table_map_event = self.table_map_event.take().unwrap();
         // let identity = map_column_change(&table_map_event, &v);
         // let schema = table_map_event.schema;
@@ -166,14 +193,28 @@ impl EventProcessor {
         //   identity,
         // })
       }
+      // The GTID of a transaction only joins the resume set once the transaction is committed, otherwise a restart
+      // in the middle of the transaction would skip its remaining rows. Statements without a XID_EVENT, e.g. DDL, are
+      // complete when the next transaction starts.
+      binlog::BinlogEvent::Gtid(evt) => {
+        self.commit_gtid()?;
+        self.pending_gtid = Some(evt.gtid);
+        self.binlog_cursor.log_position = header.log_position;
+        Ok(None)
+      }
+      binlog::BinlogEvent::Xid(_) => {
+        self.commit_gtid()?;
+        self.binlog_cursor.log_position = header.log_position;
+        Ok(None)
+      }
       binlog::BinlogEvent::Rotate(evt) => {
         self.binlog_cursor.log_file = evt.next_log_file.clone();
         self.binlog_cursor.log_position = evt.next_log_position;
-        None
+        Ok(None)
       }
       _ => {
         self.binlog_cursor.log_position = header.log_position;
-        None
+        Ok(None)
       }
     }
   }
@@ -0,0 +1,9 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use pg::pgoutput::PgOutputMessage;
+
+fuzz_target!(|data: &[u8]| {
+  let _ = PgOutputMessage::parse(Bytes::copy_from_slice(data));
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use pg::{
+  pgoutput::RelationCache,
+  wal::{parse_replication_event, OutputPlugin},
+};
+
+// The input is a sequence of backend messages, each prefixed by its type byte and a 2 bytes length, so that relations
+// cached by earlier messages are used to parse the later ones.
+fuzz_target!(|data: &[u8]| {
+  let output_plugin = OutputPlugin::PgOutput {
+    proto_version: 1,
+    publication_names: vec![],
+    messages: true,
+  };
+  let mut relations = RelationCache::default();
+
+  let mut data = data;
+  while data.len() >= 3 {
+    let op = data[0];
+    let len = usize::from(u16::from_be_bytes([data[1], data[2]])).min(data.len() - 3);
+    let buffer = Bytes::copy_from_slice(&data[3..3 + len]);
+    data = &data[3 + len..];
+
+    let _ = parse_replication_event(op, buffer, &output_plugin, &mut relations);
+  }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use pg::types::Value;
+
+// The first 4 bytes are the type oid, the remaining ones the value in both the binary and the text format.
+fuzz_target!(|data: &[u8]| {
+  if data.len() < 4 {
+    return;
+  }
+  let type_oid = i32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+  let raw = &data[4..];
+
+  let _ = Value::decode_binary(type_oid, Bytes::copy_from_slice(raw));
+  if let Ok(text) = std::str::from_utf8(raw) {
+    let _ = Value::decode_text(type_oid, text);
+  }
+});
@@ -1,38 +1,122 @@
-use bytes::Buf;
+use bytes::{Buf, BufMut, Bytes};
 use std::{collections::BTreeMap, io};
 
+use super::{
+  error::{PgError, ProtocolError},
+  query::Column,
+};
+
+macro_rules! checked_get {
+  ($($name:ident => $get:ident: $t:ty),+ $(,)?) => {
+    $(
+      fn $name(&mut self) -> io::Result<$t> {
+        self.pg_ensure_remaining(std::mem::size_of::<$t>())?;
+        Ok(self.$get())
+      }
+    )+
+  };
+}
+
+// Readers of the fields of backend messages. They return `ProtocolError`s instead of panicking when a message is
+// malformed or shorter than expected.
 pub trait BufExt: Buf {
-  fn pg_get_null_terminated_string(&mut self) -> String {
+  fn pg_ensure_remaining(&self, len: usize) -> io::Result<()> {
+    if self.remaining() < len {
+      return Err(ProtocolError::UnexpectedEof.into());
+    }
+    Ok(())
+  }
+
+  checked_get! {
+    pg_get_u8 => get_u8: u8,
+    pg_get_i8 => get_i8: i8,
+    pg_get_i16 => get_i16: i16,
+    pg_get_u16 => get_u16: u16,
+    pg_get_i32 => get_i32: i32,
+    pg_get_u32 => get_u32: u32,
+    pg_get_i64 => get_i64: i64,
+    pg_get_u64 => get_u64: u64,
+  }
+
+  fn pg_get_bytes(&mut self, len: usize) -> io::Result<Bytes> {
+    self.pg_ensure_remaining(len)?;
+    Ok(self.copy_to_bytes(len))
+  }
+
+  // Reads an Int32 length, which must not be negative.
+  fn pg_get_length(&mut self) -> io::Result<usize> {
+    match self.pg_get_i32()? {
+      len if len < 0 => Err(ProtocolError::invalid(format!("invalid length {}", len)).into()),
+      len => Ok(len as usize),
+    }
+  }
+
+  // Reads a length prefixed value, where -1 means NULL.
+  fn pg_get_nullable_bytes(&mut self) -> io::Result<Option<Bytes>> {
+    match self.pg_get_i32()? {
+      -1 => Ok(None),
+      len if len < 0 => Err(ProtocolError::invalid(format!("invalid value length {}", len)).into()),
+      len => self.pg_get_bytes(len as usize).map(Some),
+    }
+  }
+
+  fn pg_get_null_terminated_string(&mut self) -> io::Result<String> {
     match self.chunk().iter().position(|x| *x == 0x00) {
       Some(len) => {
         let mut buffer = vec![0; len];
         self.copy_to_slice(buffer.as_mut_slice());
         self.advance(1);
 
-        String::from_utf8(buffer).unwrap()
+        String::from_utf8(buffer).map_err(|_| ProtocolError::InvalidUtf8.into())
       }
-      None => panic!("missing null terminator"),
+      None => Err(ProtocolError::MissingNullTerminator.into()),
     }
   }
 
-  fn pg_get_fixed_length_string(&mut self, len: usize) -> String {
+  fn pg_get_fixed_length_string(&mut self, len: usize) -> io::Result<String> {
+    self.pg_ensure_remaining(len)?;
     let mut bytes = vec![0; len];
     self.copy_to_slice(bytes.as_mut_slice());
-    String::from_utf8(bytes).unwrap()
+    String::from_utf8(bytes).map_err(|_| ProtocolError::InvalidUtf8.into())
   }
 
-  fn pg_get_fields(&mut self) -> BTreeMap<char, String> {
+  fn pg_get_fields(&mut self) -> io::Result<BTreeMap<char, String>> {
     let mut fields = BTreeMap::new();
     loop {
-      match self.get_u8() {
+      match self.pg_get_u8()? {
         0 => break,
         token => {
-          let msg = self.pg_get_null_terminated_string();
+          let msg = self.pg_get_null_terminated_string()?;
           fields.insert(char::from(token), msg);
         }
       }
     }
-    fields
+    Ok(fields)
+  }
+
+  fn pg_get_row_description(&mut self) -> io::Result<Vec<Column>> {
+    let mut columns = Vec::new();
+    let num_columns = self.pg_get_i16()?;
+    for _i in 0..num_columns {
+      let name = self.pg_get_null_terminated_string()?;
+      let oid = self.pg_get_i32()?;
+      let attr_number = self.pg_get_i16()?;
+      let datatype_oid = self.pg_get_i32()?;
+      let datatype_size = self.pg_get_i16()?;
+      let type_modifier = self.pg_get_i32()?;
+      let format = self.pg_get_i16()?;
+
+      columns.push(Column {
+        name,
+        oid,
+        attr_number,
+        datatype_oid,
+        datatype_size,
+        type_modifier,
+        format,
+      });
+    }
+    Ok(columns)
   }
 
   fn pg_get_backend_error(&mut self) -> io::Error {
@@ -48,11 +132,9 @@ pub trait BufExt: Buf {
     //     String
     //         The field value.
     match self.pg_get_fields() {
-      fields if fields.is_empty() => io::Error::new(io::ErrorKind::InvalidData, "missing error fields from server"),
-      fields => io::Error::new(
-        io::ErrorKind::Other,
-        format!("Server error {}: {}", fields[&'C'], fields[&'M']),
-      ),
+      Ok(fields) if fields.is_empty() => ProtocolError::invalid("missing error fields from server").into(),
+      Ok(fields) => PgError::from_fields(fields).into(),
+      Err(err) => err,
     }
   }
 
@@ -69,13 +151,20 @@ pub trait BufExt: Buf {
     //     String
     //         The field value.
     match self.pg_get_fields() {
-      fields if fields.is_empty() => io::Error::new(io::ErrorKind::InvalidData, "missing error fields from server"),
-      fields => io::Error::new(
-        io::ErrorKind::Other,
-        format!("Server notice {}: {}", fields[&'C'], fields[&'M']),
-      ),
+      Ok(fields) if fields.is_empty() => ProtocolError::invalid("missing error fields from server").into(),
+      Ok(fields) => PgError::from_fields(fields).into(),
+      Err(err) => err,
     }
   }
 }
 
+pub trait BufMutExt: BufMut {
+  fn pg_put_null_terminated_string(&mut self, v: &str) {
+    self.put_slice(v.as_bytes());
+    self.put_u8(0);
+  }
+}
+
+// Blanket implementations
 impl<T> BufExt for T where T: Buf {}
+impl<T> BufMutExt for T where T: BufMut {}
@@ -0,0 +1,634 @@
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::AsyncWriteExt;
+
+use super::{buf_ext::BufExt, conn::Connection, error::ProtocolError, query::Format};
+
+// Format of the data exchanged with COPY, which must match the FORMAT option of the COPY statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CopyFormat {
+  #[default]
+  Text,
+  Csv,
+  Binary,
+}
+
+impl CopyFormat {
+  pub(crate) fn overall_format(self) -> Format {
+    match self {
+      Self::Binary => Format::Binary,
+      _ => Format::Text,
+    }
+  }
+}
+
+// Fields of a row read or written with COPY. Text and CSV fields are unescaped, binary fields are in the type's binary
+// representation.
+pub type CopyRow = Vec<Option<Bytes>>;
+
+const BINARY_SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+
+// Stream of data returned by `COPY ... TO STDOUT`. The connection can't be used for anything else until the stream
+// is fully consumed.
+#[derive(Debug)]
+pub struct CopyOut<'a> {
+  pub(crate) conn: &'a mut Connection,
+  pub(crate) format: CopyFormat,
+  pub(crate) column_formats: Vec<Format>,
+  pub(crate) binary: BinaryRowDecoder,
+  pub(crate) command_tag: Option<String>,
+  pub(crate) done: bool,
+}
+
+impl<'a> CopyOut<'a> {
+  pub fn format(&self) -> CopyFormat {
+    self.format
+  }
+
+  pub fn column_formats(&self) -> &[Format] {
+    &self.column_formats
+  }
+
+  // Command tag sent once the copy completes, e.g. `COPY 42`.
+  pub fn command_tag(&self) -> Option<&str> {
+    self.command_tag.as_deref()
+  }
+
+  // Next chunk of data as sent by the server. Each chunk holds a single row, except for the binary header and trailer.
+  pub async fn recv(&mut self) -> Option<io::Result<Bytes>> {
+    self.read_copy_data().await.transpose()
+  }
+
+  // Next row, decoded according to the copy format.
+  pub async fn recv_row(&mut self) -> Option<io::Result<CopyRow>> {
+    loop {
+      if self.format == CopyFormat::Binary {
+        match self.binary.next_row() {
+          Ok(Some(row)) => return Some(Ok(row)),
+          Ok(None) => {}
+          Err(err) => return Some(Err(err)),
+        }
+      }
+
+      let chunk = match self.read_copy_data().await {
+        Ok(Some(chunk)) => chunk,
+        Ok(None) if self.format == CopyFormat::Binary && !self.binary.is_finished() => {
+          return Some(Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "binary copy ended without a trailer",
+          )))
+        }
+        Ok(None) => return None,
+        Err(err) => return Some(Err(err)),
+      };
+
+      match self.format {
+        CopyFormat::Text => return Some(decode_text_row(&chunk)),
+        CopyFormat::Csv => return Some(decode_csv_row(&chunk)),
+        CopyFormat::Binary => self.binary.extend(&chunk),
+      }
+    }
+  }
+
+  async fn read_copy_data(&mut self) -> io::Result<Option<Bytes>> {
+    if self.done {
+      return Ok(None);
+    }
+
+    let mut error = None;
+    loop {
+      let (op, mut buffer) = self.conn.stream_read_packet().await?;
+
+      match op {
+        // CopyData (F & B)
+        b'd' if error.is_none() => return Ok(Some(buffer)),
+        // CopyDone (F & B)
+        b'c' => {}
+        b'C' => {
+          self.command_tag = Some(buffer.pg_get_null_terminated_string()?);
+        }
+        b'Z' => {
+          self.done = true;
+          return match error {
+            Some(err) => Err(err),
+            None => Ok(None),
+          };
+        }
+        b'E' => match buffer.pg_get_backend_error() {
+          err if err.kind() == io::ErrorKind::Other => {
+            error.get_or_insert(err);
+          }
+          err => return Err(err),
+        },
+        b'N' | b'd' => {}
+        code => return Err(ProtocolError::UnexpectedMessage(code).into()),
+      }
+    }
+  }
+}
+
+// Rows are buffered up to this size before being sent in a single CopyData message.
+const COPY_IN_BUFFER_SIZE: usize = 64 * 1024;
+
+// Writer for the data of a `COPY ... FROM STDIN`. The copy must be completed with `finish` or aborted with `fail` before
+// the connection can be used for anything else. Writes wait for the socket to drain, so a slow server slows the writer
+// down instead of buffering without bounds.
+#[derive(Debug)]
+pub struct CopyIn<'a> {
+  pub(crate) conn: &'a mut Connection,
+  pub(crate) format: CopyFormat,
+  pub(crate) column_formats: Vec<Format>,
+  pub(crate) buffer: BytesMut,
+  pub(crate) header: bool,
+}
+
+impl<'a> CopyIn<'a> {
+  pub fn format(&self) -> CopyFormat {
+    self.format
+  }
+
+  pub fn column_formats(&self) -> &[Format] {
+    &self.column_formats
+  }
+
+  // Sends data as is. It does not need to be aligned on rows.
+  pub async fn send(&mut self, data: impl AsRef<[u8]>) -> io::Result<()> {
+    self.flush_buffer().await?;
+    self.write_copy_data(data.as_ref()).await
+  }
+
+  // Encodes a row according to the copy format. Rows are buffered and sent in batches.
+  pub async fn send_row<T: AsRef<[u8]>>(&mut self, row: &[Option<T>]) -> io::Result<()> {
+    match self.format {
+      CopyFormat::Text => encode_text_row(&mut self.buffer, row),
+      CopyFormat::Csv => encode_csv_row(&mut self.buffer, row),
+      CopyFormat::Binary => {
+        if !self.header {
+          put_binary_header(&mut self.buffer);
+          self.header = true;
+        }
+        encode_binary_row(&mut self.buffer, row)?;
+      }
+    }
+
+    if self.buffer.len() >= COPY_IN_BUFFER_SIZE {
+      self.flush_buffer().await?;
+    }
+    Ok(())
+  }
+
+  // Completes the copy and returns the number of rows copied.
+  pub async fn finish(mut self) -> io::Result<u64> {
+    if self.format == CopyFormat::Binary {
+      if !self.header {
+        put_binary_header(&mut self.buffer);
+      }
+      self.buffer.put_i16(-1);
+    }
+    self.flush_buffer().await?;
+
+    // CopyDone (F & B)
+    self.conn.stream.write_u8(b'c').await?;
+    self.conn.stream.write_i32(4).await?;
+    self.conn.stream_flush().await?;
+
+    let command_tag = self.read_response().await?;
+    Ok(
+      command_tag
+        .as_deref()
+        .and_then(|tag| tag.rsplit(' ').next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0),
+    )
+  }
+
+  // Aborts the copy. Nothing sent so far is kept.
+  pub async fn fail(mut self, message: impl AsRef<str>) -> io::Result<()> {
+    self.conn.write_copy_fail(message.as_ref()).await?;
+    match self.read_response().await {
+      Ok(_) => Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "copy succeeded after CopyFail",
+      )),
+      Err(err) if err.kind() == io::ErrorKind::Other => Ok(()),
+      Err(err) => Err(err),
+    }
+  }
+
+  async fn flush_buffer(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() {
+      return Ok(());
+    }
+    let buffer = self.buffer.split();
+    self.write_copy_data(&buffer).await
+  }
+
+  async fn write_copy_data(&mut self, data: &[u8]) -> io::Result<()> {
+    // CopyData (F & B)
+    //     Byte1('d')
+    //         Identifies the message as COPY data.
+    //     Int32
+    //         Length of message contents in bytes, including self.
+    //     Byten
+    //         Data that forms part of a COPY data stream. Messages sent from the backend will always correspond to single data rows, but messages sent by frontends might divide the data stream arbitrarily.
+    self.conn.stream.write_u8(b'd').await?;
+    self.conn.stream.write_i32((4 + data.len()) as i32).await?;
+    self.conn.stream.write_all(data).await?;
+    self.conn.stream_flush().await
+  }
+
+  async fn read_response(&mut self) -> io::Result<Option<String>> {
+    let mut command_tag = None;
+    let mut error = None;
+    loop {
+      let (op, mut buffer) = self.conn.stream_read_packet().await?;
+
+      match op {
+        b'C' => {
+          command_tag = Some(buffer.pg_get_null_terminated_string()?);
+        }
+        b'Z' => {
+          return match error {
+            Some(err) => Err(err),
+            None => Ok(command_tag),
+          }
+        }
+        b'E' => match buffer.pg_get_backend_error() {
+          err if err.kind() == io::ErrorKind::Other => {
+            error.get_or_insert(err);
+          }
+          err => return Err(err),
+        },
+        b'N' => {}
+        code => return Err(ProtocolError::UnexpectedMessage(code).into()),
+      }
+    }
+  }
+}
+
+fn put_binary_header(b: &mut BytesMut) {
+  b.put_slice(BINARY_SIGNATURE);
+  // flags
+  b.put_i32(0);
+  // header extension area length
+  b.put_i32(0);
+}
+
+pub(crate) fn encode_text_row<T: AsRef<[u8]>>(b: &mut BytesMut, row: &[Option<T>]) {
+  for (i, field) in row.iter().enumerate() {
+    if i > 0 {
+      b.put_u8(b'\t');
+    }
+    match field {
+      None => b.put_slice(b"\\N"),
+      Some(field) => {
+        for c in field.as_ref() {
+          match c {
+            b'\\' => b.put_slice(b"\\\\"),
+            b'\t' => b.put_slice(b"\\t"),
+            b'\n' => b.put_slice(b"\\n"),
+            b'\r' => b.put_slice(b"\\r"),
+            c => b.put_u8(*c),
+          }
+        }
+      }
+    }
+  }
+  b.put_u8(b'\n');
+}
+
+pub(crate) fn encode_csv_row<T: AsRef<[u8]>>(b: &mut BytesMut, row: &[Option<T>]) {
+  for (i, field) in row.iter().enumerate() {
+    if i > 0 {
+      b.put_u8(b',');
+    }
+    if let Some(field) = field {
+      let field = field.as_ref();
+      // Empty strings are quoted, otherwise they would be read as NULL.
+      let quote = field.is_empty()
+        || field.first() == Some(&b' ')
+        || field == b"\\."
+        || field.iter().any(|c| matches!(c, b',' | b'"' | b'\n' | b'\r'));
+      if quote {
+        b.put_u8(b'"');
+        for c in field {
+          if *c == b'"' {
+            b.put_u8(b'"');
+          }
+          b.put_u8(*c);
+        }
+        b.put_u8(b'"');
+      } else {
+        b.put_slice(field);
+      }
+    }
+  }
+  b.put_u8(b'\n');
+}
+
+pub(crate) fn encode_binary_row<T: AsRef<[u8]>>(b: &mut BytesMut, row: &[Option<T>]) -> io::Result<()> {
+  let num_fields =
+    i16::try_from(row.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many fields"))?;
+  b.put_i16(num_fields);
+  for field in row {
+    match field {
+      None => b.put_i32(-1),
+      Some(field) => {
+        let field = field.as_ref();
+        let len =
+          i32::try_from(field.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "field is too large"))?;
+        b.put_i32(len);
+        b.put_slice(field);
+      }
+    }
+  }
+  Ok(())
+}
+
+// Unescapes a row in text format, e.g. `1\tfoo\\tbar\t\N\n`.
+pub(crate) fn decode_text_row(chunk: &[u8]) -> io::Result<CopyRow> {
+  let chunk = chunk.strip_suffix(b"\n").unwrap_or(chunk);
+  let chunk = chunk.strip_suffix(b"\r").unwrap_or(chunk);
+
+  chunk
+    .split(|b| *b == b'\t')
+    .map(|field| {
+      if field == b"\\N" {
+        return Ok(None);
+      }
+
+      let mut value = Vec::with_capacity(field.len());
+      let mut i = 0;
+      while i < field.len() {
+        if field[i] != b'\\' {
+          value.push(field[i]);
+          i += 1;
+          continue;
+        }
+
+        i += 1;
+        let c = *field
+          .get(i)
+          .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "trailing backslash in copy data"))?;
+        i += 1;
+        match c {
+          b'b' => value.push(0x08),
+          b'f' => value.push(0x0c),
+          b'n' => value.push(b'\n'),
+          b'r' => value.push(b'\r'),
+          b't' => value.push(b'\t'),
+          b'v' => value.push(0x0b),
+          b'0'..=b'7' => {
+            let mut n = u32::from(c - b'0');
+            let mut digits = 1;
+            while digits < 3 && matches!(field.get(i), Some(b'0'..=b'7')) {
+              n = n * 8 + u32::from(field[i] - b'0');
+              i += 1;
+              digits += 1;
+            }
+            value.push(n as u8);
+          }
+          b'x' if field.get(i).is_some_and(u8::is_ascii_hexdigit) => {
+            let start = i;
+            while i < field.len() && i - start < 2 && field[i].is_ascii_hexdigit() {
+              i += 1;
+            }
+            let hex = std::str::from_utf8(&field[start..i]).unwrap();
+            value.push(u8::from_str_radix(hex, 16).unwrap());
+          }
+          c => value.push(c),
+        }
+      }
+      Ok(Some(Bytes::from(value)))
+    })
+    .collect()
+}
+
+// Parses a row in CSV format with the default options (`,` delimiter, `"` quote and escape, unquoted empty string as
+// NULL).
+pub(crate) fn decode_csv_row(chunk: &[u8]) -> io::Result<CopyRow> {
+  let chunk = chunk.strip_suffix(b"\n").unwrap_or(chunk);
+  let chunk = chunk.strip_suffix(b"\r").unwrap_or(chunk);
+
+  let mut row = Vec::new();
+  let mut i = 0;
+  loop {
+    if chunk.get(i) == Some(&b'"') {
+      let mut value = Vec::new();
+      i += 1;
+      loop {
+        match chunk.get(i) {
+          Some(b'"') if chunk.get(i + 1) == Some(&b'"') => {
+            value.push(b'"');
+            i += 2;
+          }
+          Some(b'"') => {
+            i += 1;
+            break;
+          }
+          Some(b) => {
+            value.push(*b);
+            i += 1;
+          }
+          None => {
+            return Err(io::Error::new(
+              io::ErrorKind::InvalidData,
+              "unterminated quoted csv field",
+            ))
+          }
+        }
+      }
+      row.push(Some(Bytes::from(value)));
+    } else {
+      let start = i;
+      while i < chunk.len() && chunk[i] != b',' {
+        i += 1;
+      }
+      match &chunk[start..i] {
+        b"" => row.push(None),
+        field => row.push(Some(Bytes::copy_from_slice(field))),
+      }
+    }
+
+    match chunk.get(i) {
+      Some(b',') => i += 1,
+      None => return Ok(row),
+      Some(b) => {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("unexpected {:?} after quoted csv field", char::from(*b)),
+        ))
+      }
+    }
+  }
+}
+
+// Incremental decoder for the binary copy format, since the header and trailer are not necessarily sent in their own
+// CopyData messages.
+#[derive(Debug, Default)]
+pub(crate) struct BinaryRowDecoder {
+  buffer: BytesMut,
+  header: bool,
+  finished: bool,
+}
+
+impl BinaryRowDecoder {
+  pub(crate) fn extend(&mut self, chunk: &[u8]) {
+    self.buffer.extend_from_slice(chunk);
+  }
+
+  pub(crate) fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  pub(crate) fn next_row(&mut self) -> io::Result<Option<CopyRow>> {
+    if !self.header {
+      // 11-byte signature, then a 32-bit flags field and a 32-bit header extension area length followed by the
+      // extension area itself.
+      if self.buffer.len() < BINARY_SIGNATURE.len() + 8 {
+        return Ok(None);
+      }
+      if &self.buffer[..BINARY_SIGNATURE.len()] != BINARY_SIGNATURE {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          "invalid binary copy signature",
+        ));
+      }
+      let extension_len = (&self.buffer[BINARY_SIGNATURE.len() + 4..]).get_u32() as usize;
+      if self.buffer.len() < BINARY_SIGNATURE.len() + 8 + extension_len {
+        return Ok(None);
+      }
+      self.buffer.advance(BINARY_SIGNATURE.len() + 8 + extension_len);
+      self.header = true;
+    }
+
+    if self.finished || self.buffer.len() < 2 {
+      return Ok(None);
+    }
+
+    // Each tuple begins with a 16-bit field count, followed by a 32-bit length and the field data for each field. The
+    // file trailer consists of a 16-bit word containing -1.
+    let mut peek = &self.buffer[..];
+    let num_fields = peek.get_i16();
+    if num_fields == -1 {
+      self.buffer.advance(2);
+      self.finished = true;
+      return Ok(None);
+    } else if num_fields < -1 {
+      return Err(ProtocolError::invalid(format!("invalid field count {}", num_fields)).into());
+    }
+
+    let mut len = 2;
+    for _i in 0..num_fields {
+      if peek.len() < 4 {
+        return Ok(None);
+      }
+      let field_len = peek.get_i32();
+      len += 4;
+      if field_len < -1 {
+        return Err(ProtocolError::invalid(format!("invalid field length {}", field_len)).into());
+      } else if field_len > 0 {
+        let field_len = field_len as usize;
+        if peek.len() < field_len {
+          return Ok(None);
+        }
+        peek.advance(field_len);
+        len += field_len;
+      }
+    }
+
+    let mut tuple = self.buffer.split_to(len).freeze();
+    tuple.advance(2);
+    let mut row = Vec::with_capacity(num_fields as usize);
+    for _i in 0..num_fields {
+      match tuple.get_i32() {
+        -1 => row.push(None),
+        field_len => row.push(Some(tuple.split_to(field_len as usize))),
+      }
+    }
+    Ok(Some(row))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use bytes::{Bytes, BytesMut};
+
+  use super::{decode_csv_row, decode_text_row, encode_binary_row, encode_csv_row, encode_text_row, BinaryRowDecoder};
+
+  fn some(v: &'static [u8]) -> Option<Bytes> {
+    Some(Bytes::from_static(v))
+  }
+
+  #[test]
+  fn decodes_text_rows() {
+    assert_eq!(
+      vec![some(b"1"), some(b"a\tb\nc\\"), None, some(b""), some(b"\x01A")],
+      decode_text_row(b"1\ta\\tb\\nc\\\\\t\\N\t\t\\1\\x41\n").unwrap()
+    );
+  }
+
+  #[test]
+  fn decodes_csv_rows() {
+    assert_eq!(
+      vec![some(b"1"), some(b"a,\"b\"\nc"), None, some(b"")],
+      decode_csv_row(b"1,\"a,\"\"b\"\"\nc\",,\"\"\n").unwrap()
+    );
+    assert!(decode_csv_row(b"\"oops").is_err());
+  }
+
+  #[test]
+  fn decodes_binary_rows() {
+    let mut decoder = BinaryRowDecoder::default();
+    decoder.extend(b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0");
+    assert_eq!(None, decoder.next_row().unwrap());
+
+    decoder.extend(b"\0\x02\0\0\0\x04\0\0\0\x2a");
+    assert_eq!(None, decoder.next_row().unwrap());
+
+    decoder.extend(b"\xff\xff\xff\xff\xff\xff");
+    assert_eq!(Some(vec![some(b"\0\0\0\x2a"), None]), decoder.next_row().unwrap());
+    assert_eq!(None, decoder.next_row().unwrap());
+    assert!(decoder.is_finished());
+  }
+
+  #[test]
+  fn rejects_invalid_binary_field_counts() {
+    let mut decoder = BinaryRowDecoder::default();
+    decoder.extend(b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0");
+    decoder.extend(b"\xff\xfe");
+    assert!(decoder.next_row().is_err());
+  }
+
+  #[test]
+  fn encodes_rows() {
+    let row = [Some(&b"1"[..]), Some(&b"a\tb\\c,\"d\"\n"[..]), None, Some(&b""[..])];
+
+    let mut b = BytesMut::new();
+    encode_text_row(&mut b, &row);
+    assert_eq!(&b"1\ta\\tb\\\\c,\"d\"\\n\t\\N\t\n"[..], &b[..]);
+    assert_eq!(
+      row.map(|v| v.map(Bytes::from_static)).to_vec(),
+      decode_text_row(&b).unwrap()
+    );
+
+    let mut b = BytesMut::new();
+    encode_csv_row(&mut b, &row);
+    assert_eq!(&b"1,\"a\tb\\c,\"\"d\"\"\n\",,\"\"\n"[..], &b[..]);
+    assert_eq!(
+      row.map(|v| v.map(Bytes::from_static)).to_vec(),
+      decode_csv_row(&b).unwrap()
+    );
+
+    let mut b = BytesMut::from(&b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0"[..]);
+    encode_binary_row(&mut b, &row).unwrap();
+    b.extend_from_slice(b"\xff\xff");
+    let mut decoder = BinaryRowDecoder::default();
+    decoder.extend(&b);
+    assert_eq!(
+      Some(row.map(|v| v.map(Bytes::from_static)).to_vec()),
+      decoder.next_row().unwrap()
+    );
+  }
+}
@@ -0,0 +1,398 @@
+use std::{collections::BTreeMap, error, fmt, io};
+
+// SQLSTATE codes that are commonly handled by callers.
+// https://www.postgresql.org/docs/current/errcodes-appendix.html
+pub mod sqlstate {
+  pub const CONNECTION_EXCEPTION: &str = "08000";
+  pub const CONNECTION_FAILURE: &str = "08006";
+  pub const PROTOCOL_VIOLATION: &str = "08P01";
+  pub const FEATURE_NOT_SUPPORTED: &str = "0A000";
+  pub const DIVISION_BY_ZERO: &str = "22012";
+  pub const UNIQUE_VIOLATION: &str = "23505";
+  pub const IDLE_IN_TRANSACTION_SESSION_TIMEOUT: &str = "25P03";
+  pub const INVALID_PASSWORD: &str = "28P01";
+  pub const INVALID_AUTHORIZATION_SPECIFICATION: &str = "28000";
+  pub const SERIALIZATION_FAILURE: &str = "40001";
+  pub const DEADLOCK_DETECTED: &str = "40P01";
+  pub const SYNTAX_ERROR: &str = "42601";
+  pub const INSUFFICIENT_PRIVILEGE: &str = "42501";
+  pub const UNDEFINED_COLUMN: &str = "42703";
+  pub const UNDEFINED_FUNCTION: &str = "42883";
+  pub const UNDEFINED_TABLE: &str = "42P01";
+  pub const UNDEFINED_OBJECT: &str = "42704";
+  pub const DUPLICATE_OBJECT: &str = "42710";
+  pub const DUPLICATE_TABLE: &str = "42P07";
+  pub const OBJECT_IN_USE: &str = "55006";
+  pub const QUERY_CANCELED: &str = "57014";
+  pub const ADMIN_SHUTDOWN: &str = "57P01";
+  pub const CRASH_SHUTDOWN: &str = "57P02";
+  pub const CANNOT_CONNECT_NOW: &str = "57P03";
+  pub const INVALID_CATALOG_NAME: &str = "3D000";
+}
+
+// Broad category of an error, derived from its SQLSTATE class. Class 42 (syntax error or access rule violation) is
+// split further as it also covers missing and duplicate objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+  Warning,
+  ConnectionFailure,
+  FeatureNotSupported,
+  DataException,
+  IntegrityConstraintViolation,
+  InvalidTransactionState,
+  InvalidAuthorization,
+  TransactionRollback,
+  SyntaxError,
+  InsufficientPrivilege,
+  UndefinedObject,
+  DuplicateObject,
+  InsufficientResources,
+  ProgramLimitExceeded,
+  ObjectNotInPrerequisiteState,
+  OperatorIntervention,
+  SystemError,
+  InternalError,
+  Other,
+}
+
+impl ErrorCategory {
+  pub fn from_code(code: &str) -> Self {
+    match code {
+      // undefined_column, undefined_function, undefined_table, undefined_parameter, undefined_object,
+      // invalid_catalog_name, invalid_schema_name
+      "42703" | "42883" | "42P01" | "42P02" | "42704" | "3D000" | "3F000" => return Self::UndefinedObject,
+      // duplicate_column, duplicate_cursor, duplicate_database, duplicate_function, duplicate_prepared_statement,
+      // duplicate_schema, duplicate_table, duplicate_alias, duplicate_object
+      "42701" | "42P03" | "42P04" | "42723" | "42P05" | "42P06" | "42P07" | "42712" | "42710" => {
+        return Self::DuplicateObject
+      }
+      sqlstate::INSUFFICIENT_PRIVILEGE => return Self::InsufficientPrivilege,
+      _ => {}
+    }
+
+    match code.get(..2).unwrap_or_default() {
+      "01" | "02" => Self::Warning,
+      "08" => Self::ConnectionFailure,
+      "0A" => Self::FeatureNotSupported,
+      "22" => Self::DataException,
+      "23" => Self::IntegrityConstraintViolation,
+      "25" => Self::InvalidTransactionState,
+      "28" => Self::InvalidAuthorization,
+      "40" => Self::TransactionRollback,
+      "42" => Self::SyntaxError,
+      "53" => Self::InsufficientResources,
+      "54" => Self::ProgramLimitExceeded,
+      "55" => Self::ObjectNotInPrerequisiteState,
+      "57" => Self::OperatorIntervention,
+      "58" => Self::SystemError,
+      "XX" => Self::InternalError,
+      _ => Self::Other,
+    }
+  }
+}
+
+// Error or notice sent by the server, with every field of the ErrorResponse (or NoticeResponse) message.
+//
+// It is returned wrapped in an `io::Error`, see `PgError::from_io_error`.
+//
+// https://www.postgresql.org/docs/current/protocol-error-fields.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgError {
+  // ERROR, FATAL, or PANIC (in an error message), or WARNING, NOTICE, DEBUG, INFO, or LOG (in a notice message),
+  // possibly localized.
+  pub severity: String,
+  // Same as `severity`, but never localized. Sent by v9.6+ servers.
+  pub severity_nonlocalized: Option<String>,
+  // SQLSTATE code.
+  pub code: String,
+  pub message: String,
+  pub detail: Option<String>,
+  pub hint: Option<String>,
+  // Position of the error in the original query string, in characters starting at 1.
+  pub position: Option<u32>,
+  // Same as `position`, but for an internally generated command.
+  pub internal_position: Option<u32>,
+  pub internal_query: Option<String>,
+  // Call stack traceback of active procedural language functions and internally-generated queries.
+  pub context: Option<String>,
+  pub schema: Option<String>,
+  pub table: Option<String>,
+  pub column: Option<String>,
+  pub data_type: Option<String>,
+  pub constraint: Option<String>,
+  pub file: Option<String>,
+  pub line: Option<u32>,
+  pub routine: Option<String>,
+}
+
+impl PgError {
+  pub(crate) fn from_fields(mut fields: BTreeMap<char, String>) -> Self {
+    let mut take = |field: char| fields.remove(&field);
+    Self {
+      severity: take('S').unwrap_or_default(),
+      severity_nonlocalized: take('V'),
+      code: take('C').unwrap_or_default(),
+      message: take('M').unwrap_or_default(),
+      detail: take('D'),
+      hint: take('H'),
+      position: take('P').and_then(|v| v.parse().ok()),
+      internal_position: take('p').and_then(|v| v.parse().ok()),
+      internal_query: take('q'),
+      context: take('W'),
+      schema: take('s'),
+      table: take('t'),
+      column: take('c'),
+      data_type: take('d'),
+      constraint: take('n'),
+      file: take('F'),
+      line: take('L').and_then(|v| v.parse().ok()),
+      routine: take('R'),
+    }
+  }
+
+  // Server error carried by an `io::Error`, if any.
+  pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+    err.get_ref().and_then(|err| err.downcast_ref::<Self>())
+  }
+
+  pub fn code(&self) -> &str {
+    &self.code
+  }
+
+  pub fn category(&self) -> ErrorCategory {
+    ErrorCategory::from_code(&self.code)
+  }
+
+  fn nonlocalized_severity(&self) -> &str {
+    self.severity_nonlocalized.as_deref().unwrap_or(&self.severity)
+  }
+
+  // Whether this is an error rather than a notice.
+  pub fn is_error(&self) -> bool {
+    !matches!(
+      self.nonlocalized_severity(),
+      "WARNING" | "NOTICE" | "DEBUG" | "INFO" | "LOG"
+    )
+  }
+
+  // FATAL and PANIC errors end the session, the server closes the connection right after sending them.
+  pub fn is_fatal(&self) -> bool {
+    matches!(self.nonlocalized_severity(), "FATAL" | "PANIC")
+  }
+
+  // Errors that end the session are `ConnectionAborted`, whatever their code, e.g. FATAL 57P01 admin_shutdown or
+  // 25P03 idle_in_transaction_session_timeout. The session goes on after other errors and notices.
+  pub(crate) fn io_error_kind(&self) -> io::ErrorKind {
+    if self.is_fatal() {
+      io::ErrorKind::ConnectionAborted
+    } else {
+      io::ErrorKind::Other
+    }
+  }
+}
+
+impl fmt::Display for PgError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let kind = if self.is_error() { "error" } else { "notice" };
+    write!(f, "Server {} {}: {}", kind, self.code, self.message)
+  }
+}
+
+impl error::Error for PgError {}
+
+impl From<PgError> for io::Error {
+  fn from(err: PgError) -> Self {
+    io::Error::new(err.io_error_kind(), err)
+  }
+}
+
+// Malformed or unexpected message received from the server. It is returned wrapped in an `io::Error` of kind
+// `InvalidData`, see `ProtocolError::from_io_error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+  // Message that is not expected at this point of the protocol flow, identified by its type byte.
+  UnexpectedMessage(u8),
+  UnexpectedAuthentication(i32),
+  // The message ended before all of its fields were read.
+  UnexpectedEof,
+  MissingNullTerminator,
+  InvalidUtf8,
+  // Any other malformed field, e.g. a negative length.
+  Invalid(String),
+}
+
+impl ProtocolError {
+  pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+    err.get_ref().and_then(|err| err.downcast_ref::<Self>())
+  }
+
+  pub(crate) fn invalid(msg: impl Into<String>) -> Self {
+    Self::Invalid(msg.into())
+  }
+}
+
+impl fmt::Display for ProtocolError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnexpectedMessage(op) => write!(f, "Unexpected backend message: {:?}", char::from(*op)),
+      Self::UnexpectedAuthentication(code) => write!(f, "Unexpected backend authentication code {}", code),
+      Self::UnexpectedEof => write!(f, "message is shorter than its fields"),
+      Self::MissingNullTerminator => write!(f, "missing null terminator"),
+      Self::InvalidUtf8 => write!(f, "string is not valid utf-8"),
+      Self::Invalid(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+impl error::Error for ProtocolError {}
+
+impl From<ProtocolError> for io::Error {
+  fn from(err: ProtocolError) -> Self {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::{collections::BTreeMap, io};
+
+  use super::{sqlstate, ErrorCategory, PgError};
+
+  #[test]
+  fn parses_fields() {
+    let fields = BTreeMap::from([
+      ('S', "ERREUR".to_string()),
+      ('V', "ERROR".to_string()),
+      ('C', "23505".to_string()),
+      (
+        'M',
+        "duplicate key value violates unique constraint \"accounts_pkey\"".to_string(),
+      ),
+      ('D', "Key (id)=(1) already exists.".to_string()),
+      ('s', "public".to_string()),
+      ('t', "accounts".to_string()),
+      ('n', "accounts_pkey".to_string()),
+      ('L', "664".to_string()),
+      ('R', "_bt_check_unique".to_string()),
+    ]);
+    let err = PgError::from_fields(fields);
+    assert_eq!("ERREUR", err.severity);
+    assert!(err.is_error());
+    assert!(!err.is_fatal());
+    assert_eq!(sqlstate::UNIQUE_VIOLATION, err.code());
+    assert_eq!(ErrorCategory::IntegrityConstraintViolation, err.category());
+    assert_eq!(Some("Key (id)=(1) already exists."), err.detail.as_deref());
+    assert_eq!(Some("accounts"), err.table.as_deref());
+    assert_eq!(Some("accounts_pkey"), err.constraint.as_deref());
+    assert_eq!(Some(664), err.line);
+    assert_eq!(None, err.position);
+
+    let err = io::Error::from(err);
+    assert_eq!(io::ErrorKind::Other, err.kind());
+    assert_eq!(
+      "Server error 23505: duplicate key value violates unique constraint \"accounts_pkey\"",
+      err.to_string()
+    );
+    assert_eq!(
+      Some("accounts_pkey"),
+      PgError::from_io_error(&err).unwrap().constraint.as_deref()
+    );
+  }
+
+  #[test]
+  fn displays_notices() {
+    let err = PgError {
+      severity: "NOTICE".to_string(),
+      code: "42P07".to_string(),
+      message: "relation \"accounts\" already exists, skipping".to_string(),
+      ..Default::default()
+    };
+    assert!(!err.is_error());
+    assert_eq!(
+      "Server notice 42P07: relation \"accounts\" already exists, skipping",
+      err.to_string()
+    );
+  }
+
+  #[test]
+  fn categorizes_codes() {
+    assert_eq!(
+      ErrorCategory::ConnectionFailure,
+      ErrorCategory::from_code(sqlstate::CONNECTION_FAILURE)
+    );
+    assert_eq!(
+      ErrorCategory::InsufficientPrivilege,
+      ErrorCategory::from_code(sqlstate::INSUFFICIENT_PRIVILEGE)
+    );
+    assert_eq!(
+      ErrorCategory::UndefinedObject,
+      ErrorCategory::from_code(sqlstate::UNDEFINED_OBJECT)
+    );
+    assert_eq!(
+      ErrorCategory::UndefinedObject,
+      ErrorCategory::from_code(sqlstate::UNDEFINED_TABLE)
+    );
+    assert_eq!(
+      ErrorCategory::UndefinedObject,
+      ErrorCategory::from_code(sqlstate::UNDEFINED_COLUMN)
+    );
+    assert_eq!(
+      ErrorCategory::UndefinedObject,
+      ErrorCategory::from_code(sqlstate::UNDEFINED_FUNCTION)
+    );
+    assert_eq!(
+      ErrorCategory::DuplicateObject,
+      ErrorCategory::from_code(sqlstate::DUPLICATE_OBJECT)
+    );
+    assert_eq!(
+      ErrorCategory::DuplicateObject,
+      ErrorCategory::from_code(sqlstate::DUPLICATE_TABLE)
+    );
+    assert_eq!(
+      ErrorCategory::SyntaxError,
+      ErrorCategory::from_code(sqlstate::SYNTAX_ERROR)
+    );
+    assert_eq!(
+      ErrorCategory::ObjectNotInPrerequisiteState,
+      ErrorCategory::from_code(sqlstate::OBJECT_IN_USE)
+    );
+    assert_eq!(
+      ErrorCategory::InvalidAuthorization,
+      ErrorCategory::from_code(sqlstate::INVALID_PASSWORD)
+    );
+    assert_eq!(
+      ErrorCategory::OperatorIntervention,
+      ErrorCategory::from_code(sqlstate::ADMIN_SHUTDOWN)
+    );
+    assert_eq!(ErrorCategory::Other, ErrorCategory::from_code(""));
+  }
+
+  #[test]
+  fn maps_io_error_kinds() {
+    let err = |severity: &str, code: &str| {
+      io::Error::from(PgError {
+        severity: severity.to_string(),
+        code: code.to_string(),
+        ..Default::default()
+      })
+    };
+    assert_eq!(
+      io::ErrorKind::ConnectionAborted,
+      err("FATAL", sqlstate::ADMIN_SHUTDOWN).kind()
+    );
+    assert_eq!(
+      io::ErrorKind::ConnectionAborted,
+      err("FATAL", sqlstate::CONNECTION_FAILURE).kind()
+    );
+    assert_eq!(
+      io::ErrorKind::ConnectionAborted,
+      err("FATAL", sqlstate::IDLE_IN_TRANSACTION_SESSION_TIMEOUT).kind()
+    );
+    assert_eq!(io::ErrorKind::ConnectionAborted, err("PANIC", "XX000").kind());
+    assert_eq!(io::ErrorKind::Other, err("ERROR", sqlstate::QUERY_CANCELED).kind());
+    assert_eq!(
+      io::ErrorKind::Other,
+      err("WARNING", sqlstate::CONNECTION_FAILURE).kind()
+    );
+  }
+}
@@ -6,6 +6,12 @@ pub use openssl;
 mod buf_ext;
 pub mod cancel;
 pub mod conn;
+pub mod copy;
+pub mod error;
+pub mod pgoutput;
+pub mod pool;
 pub mod query;
+pub mod snapshot;
 mod stream;
+pub mod types;
 pub mod wal;
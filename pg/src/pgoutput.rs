@@ -0,0 +1,546 @@
+use std::{collections::HashMap, io};
+
+use bytes::Bytes;
+
+use super::{buf_ext::BufExt, error::ProtocolError, wal::Lsn};
+
+// https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+#[derive(Debug)]
+pub enum PgOutputMessage {
+  Begin {
+    final_lsn: Lsn,
+    commit_timestamp: i64,
+    xid: i32,
+  },
+  Commit {
+    flags: i8,
+    commit_lsn: Lsn,
+    end_lsn: Lsn,
+    commit_timestamp: i64,
+  },
+  Origin {
+    commit_lsn: Lsn,
+    name: String,
+  },
+  Relation(Relation),
+  Type {
+    id: i32,
+    namespace: String,
+    name: String,
+  },
+  Insert {
+    relation_id: i32,
+    new: TupleData,
+  },
+  Update {
+    relation_id: i32,
+    identity: Option<Identity>,
+    new: TupleData,
+  },
+  Delete {
+    relation_id: i32,
+    identity: Identity,
+  },
+  Truncate {
+    cascade: bool,
+    restart_identity: bool,
+    relation_ids: Vec<i32>,
+  },
+  Message {
+    transactional: bool,
+    lsn: Lsn,
+    prefix: String,
+    content: Bytes,
+  },
+}
+
+impl PgOutputMessage {
+  pub fn parse(mut b: Bytes) -> io::Result<Self> {
+    match b.pg_get_u8()? {
+      b'B' => {
+        // Begin
+        //     Int64 (XLogRecPtr)
+        //         The final LSN of the transaction.
+        //     Int64 (TimestampTz)
+        //         Commit timestamp of the transaction. The value is in number of microseconds since PostgreSQL epoch (2000-01-01).
+        //     Int32 (TransactionId)
+        //         Xid of the transaction.
+        let final_lsn = Lsn(b.pg_get_u64()?);
+        let commit_timestamp = b.pg_get_i64()?;
+        let xid = b.pg_get_i32()?;
+        Ok(Self::Begin {
+          final_lsn,
+          commit_timestamp,
+          xid,
+        })
+      }
+      b'C' => {
+        // Commit
+        //     Int8(0)
+        //         Flags; currently unused.
+        //     Int64 (XLogRecPtr)
+        //         The LSN of the commit.
+        //     Int64 (XLogRecPtr)
+        //         The end LSN of the transaction.
+        //     Int64 (TimestampTz)
+        //         Commit timestamp of the transaction.
+        let flags = b.pg_get_i8()?;
+        let commit_lsn = Lsn(b.pg_get_u64()?);
+        let end_lsn = Lsn(b.pg_get_u64()?);
+        let commit_timestamp = b.pg_get_i64()?;
+        Ok(Self::Commit {
+          flags,
+          commit_lsn,
+          end_lsn,
+          commit_timestamp,
+        })
+      }
+      b'O' => {
+        // Origin
+        //     Int64 (XLogRecPtr)
+        //         The LSN of the commit on the origin server.
+        //     String
+        //         Name of the origin.
+        let commit_lsn = Lsn(b.pg_get_u64()?);
+        let name = b.pg_get_null_terminated_string()?;
+        Ok(Self::Origin { commit_lsn, name })
+      }
+      b'R' => Relation::parse(&mut b).map(Self::Relation),
+      b'Y' => {
+        // Type
+        //     Int32 (Oid)
+        //         OID of the data type.
+        //     String
+        //         Namespace (empty string for pg_catalog).
+        //     String
+        //         Name of the data type.
+        let id = b.pg_get_i32()?;
+        let namespace = b.pg_get_null_terminated_string()?;
+        let name = b.pg_get_null_terminated_string()?;
+        Ok(Self::Type { id, namespace, name })
+      }
+      b'I' => {
+        // Insert
+        //     Int32 (Oid)
+        //         OID of the relation corresponding to the ID in the relation message.
+        //     Byte1('N')
+        //         Identifies the following TupleData message as a new tuple.
+        let relation_id = b.pg_get_i32()?;
+        match b.pg_get_u8()? {
+          b'N' => {
+            let new = parse_tuple_data(&mut b)?;
+            Ok(Self::Insert { relation_id, new })
+          }
+          code => Err(unexpected_tuple_type(code)),
+        }
+      }
+      b'U' => {
+        // Update
+        //     Int32 (Oid)
+        //         OID of the relation corresponding to the ID in the relation message.
+        //     Byte1('K') | Byte1('O')
+        //         Optional. Identifies the following TupleData submessage as a key (K) or as an old tuple (O).
+        //     Byte1('N')
+        //         Identifies the following TupleData message as a new tuple.
+        let relation_id = b.pg_get_i32()?;
+        let identity = match b.pg_get_u8()? {
+          b'K' => Some(Identity::Key(parse_tuple_data(&mut b)?)),
+          b'O' => Some(Identity::Full(parse_tuple_data(&mut b)?)),
+          b'N' => None,
+          code => return Err(unexpected_tuple_type(code)),
+        };
+        if identity.is_some() {
+          match b.pg_get_u8()? {
+            b'N' => {}
+            code => return Err(unexpected_tuple_type(code)),
+          }
+        }
+        let new = parse_tuple_data(&mut b)?;
+        Ok(Self::Update {
+          relation_id,
+          identity,
+          new,
+        })
+      }
+      b'D' => {
+        // Delete
+        //     Int32 (Oid)
+        //         OID of the relation corresponding to the ID in the relation message.
+        //     Byte1('K') | Byte1('O')
+        //         Identifies the following TupleData submessage as a key (K) or as an old tuple (O).
+        let relation_id = b.pg_get_i32()?;
+        let identity = match b.pg_get_u8()? {
+          b'K' => Identity::Key(parse_tuple_data(&mut b)?),
+          b'O' => Identity::Full(parse_tuple_data(&mut b)?),
+          code => return Err(unexpected_tuple_type(code)),
+        };
+        Ok(Self::Delete { relation_id, identity })
+      }
+      b'T' => {
+        // Truncate
+        //     Int32
+        //         Number of relations
+        //     Int8
+        //         Option bits for TRUNCATE: 1 for CASCADE, 2 for RESTART IDENTITY
+        //     Int32 (Oid)
+        //         OID of the relation corresponding to the ID in the relation message. This field is repeated for each relation.
+        let num_relations = b.pg_get_i32()?;
+        let options = b.pg_get_i8()?;
+        let mut relation_ids = Vec::new();
+        for _i in 0..num_relations {
+          relation_ids.push(b.pg_get_i32()?);
+        }
+        Ok(Self::Truncate {
+          cascade: options & 1 != 0,
+          restart_identity: options & 2 != 0,
+          relation_ids,
+        })
+      }
+      b'M' => {
+        // Message
+        //     Int8
+        //         Flags; Either 0 for no flags or 1 if the logical decoding message is transactional.
+        //     Int64 (XLogRecPtr)
+        //         The LSN of the logical decoding message.
+        //     String
+        //         The prefix of the logical decoding message.
+        //     Int32
+        //         Length of the content.
+        //     Byten
+        //         The content of the logical decoding message.
+        let flags = b.pg_get_i8()?;
+        let lsn = Lsn(b.pg_get_u64()?);
+        let prefix = b.pg_get_null_terminated_string()?;
+        let len = b.pg_get_length()?;
+        let content = b.pg_get_bytes(len)?;
+        Ok(Self::Message {
+          transactional: flags == 1,
+          lsn,
+          prefix,
+          content,
+        })
+      }
+      code => Err(ProtocolError::invalid(format!("Unexpected pgoutput message: {:?}", char::from(code))).into()),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Relation {
+  pub id: i32,
+  pub namespace: String,
+  pub name: String,
+  pub replica_identity: ReplicaIdentity,
+  pub columns: Vec<RelationColumn>,
+}
+
+impl Relation {
+  fn parse(b: &mut Bytes) -> io::Result<Self> {
+    // Relation
+    //     Int32 (Oid)
+    //         OID of the relation.
+    //     String
+    //         Namespace (empty string for pg_catalog).
+    //     String
+    //         Relation name.
+    //     Int8
+    //         Replica identity setting for the relation (same as relreplident in pg_class).
+    //     Int16
+    //         Number of columns.
+    //     Next, the following message part appears for each column included in the publication:
+    //     Int8
+    //         Flags for the column. Currently can be either 0 for no flags or 1 which marks the column as part of the key.
+    //     String
+    //         Name of the column.
+    //     Int32 (Oid)
+    //         OID of the column's data type.
+    //     Int32
+    //         Type modifier of the column (atttypmod).
+    let id = b.pg_get_i32()?;
+    let namespace = b.pg_get_null_terminated_string()?;
+    let name = b.pg_get_null_terminated_string()?;
+    let replica_identity = match b.pg_get_u8()? {
+      b'd' => ReplicaIdentity::Default,
+      b'n' => ReplicaIdentity::Nothing,
+      b'f' => ReplicaIdentity::Full,
+      b'i' => ReplicaIdentity::Index,
+      code => {
+        return Err(ProtocolError::invalid(format!("Unexpected replica identity: {:?}", char::from(code))).into())
+      }
+    };
+    let num_columns = b.pg_get_i16()?;
+    let mut columns = Vec::new();
+    for _i in 0..num_columns {
+      let flags = b.pg_get_i8()?;
+      let name = b.pg_get_null_terminated_string()?;
+      let type_oid = b.pg_get_i32()?;
+      let type_modifier = b.pg_get_i32()?;
+      columns.push(RelationColumn {
+        is_key: flags & 1 != 0,
+        name,
+        type_oid,
+        type_modifier,
+      });
+    }
+    Ok(Self {
+      id,
+      namespace,
+      name,
+      replica_identity,
+      columns,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaIdentity {
+  Default,
+  Nothing,
+  Full,
+  Index,
+}
+
+#[derive(Debug, Clone)]
+pub struct RelationColumn {
+  pub is_key: bool,
+  pub name: String,
+  pub type_oid: i32,
+  pub type_modifier: i32,
+}
+
+#[derive(Debug)]
+pub enum Identity {
+  Key(TupleData),
+  Full(TupleData),
+}
+
+impl Identity {
+  pub fn into_tuple_data(self) -> TupleData {
+    match self {
+      Self::Key(v) => v,
+      Self::Full(v) => v,
+    }
+  }
+}
+
+pub type TupleData = Vec<TupleValue>;
+
+#[derive(Debug, PartialEq)]
+pub enum TupleValue {
+  Null,
+  UnchangedToast,
+  Text(String),
+  Binary(Bytes),
+}
+
+fn parse_tuple_data(b: &mut Bytes) -> io::Result<TupleData> {
+  // TupleData
+  //     Int16
+  //         Number of columns.
+  //     Next, one of the following submessages appears for each column (except generated columns):
+  //     Byte1('n')
+  //         Identifies the data as NULL value.
+  //     Byte1('u')
+  //         Identifies unchanged TOASTed value (the actual value is not sent).
+  //     Byte1('t')
+  //         Identifies the data as text formatted value.
+  //     Byte1('b')
+  //         Identifies the data as binary formatted value.
+  //     Int32
+  //         Length of the column value.
+  //     Byten
+  //         The value of the column, either in binary or in text format.
+  let num_columns = b.pg_get_i16()?;
+  let mut values = Vec::new();
+  for _i in 0..num_columns {
+    match b.pg_get_u8()? {
+      b'n' => values.push(TupleValue::Null),
+      b'u' => values.push(TupleValue::UnchangedToast),
+      b't' => {
+        let len = b.pg_get_length()?;
+        let value = b.pg_get_fixed_length_string(len)?;
+        values.push(TupleValue::Text(value));
+      }
+      b'b' => {
+        let len = b.pg_get_length()?;
+        values.push(TupleValue::Binary(b.pg_get_bytes(len)?));
+      }
+      code => return Err(ProtocolError::invalid(format!("Unexpected tuple value: {:?}", char::from(code))).into()),
+    }
+  }
+  Ok(values)
+}
+
+fn unexpected_tuple_type(code: u8) -> io::Error {
+  ProtocolError::invalid(format!("Unexpected tuple type: {:?}", char::from(code))).into()
+}
+
+/// Relations seen on the stream, keyed by relation OID. pgoutput only sends a Relation message before the first
+/// change of a relation (or after its definition changed), so it must be kept for the lifetime of the stream.
+#[derive(Debug, Default)]
+pub struct RelationCache {
+  relations: HashMap<i32, Relation>,
+}
+
+impl RelationCache {
+  pub fn get(&self, id: i32) -> Option<&Relation> {
+    self.relations.get(&id)
+  }
+
+  pub(crate) fn insert(&mut self, relation: Relation) {
+    self.relations.insert(relation.id, relation);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use bytes::Bytes;
+
+  use super::{Identity, Lsn, PgOutputMessage, ProtocolError, ReplicaIdentity, TupleValue};
+
+  #[test]
+  fn parses_begin() {
+    const BEGIN: &[u8] = b"B\x00\x00\x00\x00\x01\x6a\x8f\x48\x00\x02\x9c\x1f\x4d\x3b\x5e\x7a\x00\x00\x02\xe6";
+
+    match PgOutputMessage::parse(BEGIN.into()).unwrap() {
+      PgOutputMessage::Begin {
+        final_lsn,
+        commit_timestamp,
+        xid,
+      } => {
+        assert_eq!(Lsn(0x016a8f48), final_lsn);
+        assert_eq!(0x029c1f4d3b5e7a, commit_timestamp);
+        assert_eq!(742, xid);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_relation() {
+    const RELATION: &[u8] = b"R\x00\x00\x40\x01public\x00users\x00d\x00\x02\
+                              \x01id\x00\x00\x00\x00\x17\xff\xff\xff\xff\
+                              \x00name\x00\x00\x00\x04\x13\x00\x00\x01\x03";
+
+    match PgOutputMessage::parse(RELATION.into()).unwrap() {
+      PgOutputMessage::Relation(relation) => {
+        assert_eq!(16385, relation.id);
+        assert_eq!("public", relation.namespace);
+        assert_eq!("users", relation.name);
+        assert_eq!(ReplicaIdentity::Default, relation.replica_identity);
+        assert_eq!(2, relation.columns.len());
+        assert!(relation.columns[0].is_key);
+        assert_eq!("id", relation.columns[0].name);
+        assert_eq!(23, relation.columns[0].type_oid);
+        assert_eq!(-1, relation.columns[0].type_modifier);
+        assert!(!relation.columns[1].is_key);
+        assert_eq!("name", relation.columns[1].name);
+        assert_eq!(1043, relation.columns[1].type_oid);
+        assert_eq!(259, relation.columns[1].type_modifier);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_insert() {
+    const INSERT: &[u8] = b"I\x00\x00\x40\x01N\x00\x03t\x00\x00\x00\x011t\x00\x00\x00\x03bobn";
+
+    match PgOutputMessage::parse(INSERT.into()).unwrap() {
+      PgOutputMessage::Insert { relation_id, new } => {
+        assert_eq!(16385, relation_id);
+        assert_eq!(
+          vec![
+            TupleValue::Text("1".to_string()),
+            TupleValue::Text("bob".to_string()),
+            TupleValue::Null
+          ],
+          new
+        );
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_update() {
+    const UPDATE: &[u8] = b"U\x00\x00\x40\x01K\x00\x02t\x00\x00\x00\x011n\
+                            N\x00\x02t\x00\x00\x00\x011u";
+
+    match PgOutputMessage::parse(UPDATE.into()).unwrap() {
+      PgOutputMessage::Update {
+        relation_id,
+        identity: Some(Identity::Key(key)),
+        new,
+      } => {
+        assert_eq!(16385, relation_id);
+        assert_eq!(vec![TupleValue::Text("1".to_string()), TupleValue::Null], key);
+        assert_eq!(vec![TupleValue::Text("1".to_string()), TupleValue::UnchangedToast], new);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_truncate() {
+    const TRUNCATE: &[u8] = b"T\x00\x00\x00\x02\x03\x00\x00\x40\x01\x00\x00\x40\x02";
+
+    match PgOutputMessage::parse(TRUNCATE.into()).unwrap() {
+      PgOutputMessage::Truncate {
+        cascade,
+        restart_identity,
+        relation_ids,
+      } => {
+        assert!(cascade);
+        assert!(restart_identity);
+        assert_eq!(vec![16385, 16386], relation_ids);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn parses_message() {
+    const MESSAGE: &[u8] = b"M\x01\x00\x00\x00\x00\x01\x6a\x8f\x48foo\x00\x00\x00\x00\x03bar";
+
+    match PgOutputMessage::parse(MESSAGE.into()).unwrap() {
+      PgOutputMessage::Message {
+        transactional,
+        lsn,
+        prefix,
+        content,
+      } => {
+        assert!(transactional);
+        assert_eq!(Lsn(0x016a8f48), lsn);
+        assert_eq!("foo", prefix);
+        assert_eq!(&b"bar"[..], &content[..]);
+      }
+      unexpected => panic!("unexpected {:?}", unexpected),
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_messages() {
+    const MESSAGES: &[&[u8]] = &[
+      b"B\x00\x00\x00\x00\x01\x6a\x8f\x48\x00\x02\x9c\x1f\x4d\x3b\x5e\x7a\x00\x00\x02\xe6",
+      b"I\x00\x00\x40\x01N\x00\x03t\x00\x00\x00\x011t\x00\x00\x00\x03bobn",
+      b"M\x01\x00\x00\x00\x00\x01\x6a\x8f\x48foo\x00\x00\x00\x00\x03bar",
+    ];
+
+    for message in MESSAGES {
+      for len in 0..message.len() {
+        let err = PgOutputMessage::parse(Bytes::copy_from_slice(&message[..len])).unwrap_err();
+        assert!(ProtocolError::from_io_error(&err).is_some(), "{:?}", err);
+      }
+    }
+
+    let err = PgOutputMessage::parse(Bytes::from_static(b"I\x00\x00\x40\x01N\x00\x01t\xff\xff\xff\xfe")).unwrap_err();
+    assert_eq!(
+      Some(&ProtocolError::invalid("invalid length -2")),
+      ProtocolError::from_io_error(&err)
+    );
+
+    let err = PgOutputMessage::parse(Bytes::from_static(b"?")).unwrap_err();
+    assert!(ProtocolError::from_io_error(&err).is_some());
+  }
+}
@@ -4,12 +4,14 @@ use std::{
   slice::{ChunksExact, ChunksExactMut},
 };
 
-use super::wal::WalCursor;
+use bytes::Bytes;
+
+use super::wal::{Lsn, WalCursor};
 
 #[derive(Debug)]
 pub struct IdentifySystem {
   pub systemid: String,
-  pub timeline: u64,
+  pub timeline: u32,
   pub wal_cursor: WalCursor,
   pub dbname: Option<String>,
 }
@@ -17,12 +19,83 @@ pub struct IdentifySystem {
 #[derive(Debug)]
 pub struct CreateReplicationSlot {
   pub slot_name: String,
-  pub consistent_point: WalCursor,
+  pub consistent_point: Lsn,
   pub snapshot_name: Option<String>,
   pub output_plugin: Option<String>,
 }
 
 #[derive(Debug)]
+pub struct TimelineHistory {
+  pub timeline: u32,
+  pub filename: String,
+  pub entries: Vec<TimelineHistoryEntry>,
+}
+
+// Asynchronous notification raised by NOTIFY (or pg_notify) on a channel the connection is listening to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+  // Process ID of the notifying backend.
+  pub pid: i32,
+  pub channel: String,
+  pub payload: String,
+}
+
+// One line of a timeline history file: the parent timeline, the position where the switch happened and the reason.
+#[derive(Debug, PartialEq)]
+pub struct TimelineHistoryEntry {
+  pub timeline: u32,
+  pub switchpoint: Lsn,
+  pub reason: String,
+}
+
+impl TimelineHistory {
+  pub(crate) fn parse(timeline: u32, filename: String, content: &str) -> io::Result<Self> {
+    let mut entries = Vec::new();
+
+    for line in content.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let mut fields = line.splitn(3, '\t');
+      let parent = fields.next().and_then(|v| v.trim().parse().ok());
+      let switchpoint = fields.next().and_then(|v| v.trim().parse().ok());
+      let reason = fields.next().unwrap_or_default().trim().to_string();
+
+      match (parent, switchpoint) {
+        (Some(timeline), Some(switchpoint)) => entries.push(TimelineHistoryEntry {
+          timeline,
+          switchpoint,
+          reason,
+        }),
+        _ => {
+          return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid timeline history line: {:?}", line),
+          ))
+        }
+      }
+    }
+
+    Ok(Self {
+      timeline,
+      filename,
+      entries,
+    })
+  }
+
+  // Position at which the given ancestor timeline was switched away from, if it is part of this history.
+  pub fn switchpoint(&self, timeline: u32) -> Option<Lsn> {
+    self
+      .entries
+      .iter()
+      .find(|entry| entry.timeline == timeline)
+      .map(|entry| entry.switchpoint)
+  }
+}
+
+#[derive(Debug, Clone)]
 pub struct Column {
   pub name: String,
   pub oid: i32,
@@ -129,3 +202,186 @@ impl SelectQueryResult {
     }
   }
 }
+
+// Format code of parameters and result columns in the extended query protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+  #[default]
+  Text,
+  Binary,
+}
+
+impl Format {
+  pub(crate) fn code(self) -> i16 {
+    match self {
+      Self::Text => 0,
+      Self::Binary => 1,
+    }
+  }
+}
+
+// A statement prepared with `Connection::prepare`. The unnamed statement (empty name) only lives until the next
+// unnamed statement is prepared or a simple query is sent.
+#[derive(Debug)]
+pub struct Statement {
+  pub name: String,
+  pub param_types: Vec<i32>,
+  pub columns: Vec<Column>,
+}
+
+// A portal bound with `Connection::bind`. Portals are closed at the end of the transaction they were bound in, so
+// named portals only make sense inside a transaction block.
+#[derive(Debug)]
+pub struct Portal {
+  pub name: String,
+  pub columns: Vec<Column>,
+}
+
+// Parameter value sent with Bind. Text values are parsed by the server according to the parameter type, binary values
+// must be in the type's binary representation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+  Null,
+  Text(String),
+  Binary(Bytes),
+}
+
+impl Param {
+  pub(crate) fn format(&self) -> Format {
+    match self {
+      Self::Binary(_) => Format::Binary,
+      _ => Format::Text,
+    }
+  }
+}
+
+impl From<&str> for Param {
+  fn from(v: &str) -> Self {
+    Self::Text(v.to_string())
+  }
+}
+
+impl From<String> for Param {
+  fn from(v: String) -> Self {
+    Self::Text(v)
+  }
+}
+
+impl From<Bytes> for Param {
+  fn from(v: Bytes) -> Self {
+    Self::Binary(v)
+  }
+}
+
+impl From<bool> for Param {
+  fn from(v: bool) -> Self {
+    Self::Text(if v { "t" } else { "f" }.to_string())
+  }
+}
+
+macro_rules! impl_param_from_display {
+  ($($t:ty),*) => {
+    $(
+      impl From<$t> for Param {
+        fn from(v: $t) -> Self {
+          Self::Text(v.to_string())
+        }
+      }
+    )*
+  };
+}
+
+impl_param_from_display!(i16, i32, i64, u32, f32, f64);
+
+impl<T> From<Option<T>> for Param
+where
+  T: Into<Param>,
+{
+  fn from(v: Option<T>) -> Self {
+    v.map(Into::into).unwrap_or(Self::Null)
+  }
+}
+
+// Rows returned by the extended query protocol. Values are kept as sent by the server, in the format requested for
+// each column.
+#[derive(Debug, Default)]
+pub struct Rows {
+  pub columns: Vec<Column>,
+  pub values: Vec<Option<Bytes>>,
+  // None when the portal was suspended because it reached the requested number of rows.
+  pub command_tag: Option<String>,
+}
+
+impl Rows {
+  pub fn columns(&self) -> &[Column] {
+    &self.columns
+  }
+
+  pub fn row(&self, i: usize) -> &[Option<Bytes>] {
+    let len = self.columns.len();
+    let start = i * len;
+    let end = start + len;
+    &self.values[start..end]
+  }
+
+  pub fn rows_len(&self) -> usize {
+    if !self.columns.is_empty() {
+      self.values.len() / self.columns.len()
+    } else {
+      0
+    }
+  }
+
+  pub fn rows(&self) -> Option<ChunksExact<'_, Option<Bytes>>> {
+    if !self.columns.is_empty() {
+      Some(self.values.chunks_exact(self.columns.len()))
+    } else {
+      None
+    }
+  }
+
+  pub fn is_suspended(&self) -> bool {
+    self.command_tag.is_none()
+  }
+
+  // Number of rows affected according to the command tag (e.g. `INSERT 0 5`, `UPDATE 3`, `SELECT 2`).
+  pub fn rows_affected(&self) -> Option<u64> {
+    self
+      .command_tag
+      .as_deref()
+      .and_then(|tag| tag.rsplit(' ').next())
+      .and_then(|n| n.parse().ok())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{TimelineHistory, TimelineHistoryEntry};
+  use crate::wal::Lsn;
+
+  #[test]
+  fn parses_timeline_history() {
+    let content = "1\t0/3000158\tno recovery target specified\n\n# comment\n2\t0/5000000\tat restore point \"foo\"\n";
+    let history = TimelineHistory::parse(3, "00000003.history".to_string(), content).unwrap();
+
+    assert_eq!(3, history.timeline);
+    assert_eq!(
+      vec![
+        TimelineHistoryEntry {
+          timeline: 1,
+          switchpoint: Lsn(0x3000158),
+          reason: "no recovery target specified".to_string(),
+        },
+        TimelineHistoryEntry {
+          timeline: 2,
+          switchpoint: Lsn(0x5000000),
+          reason: "at restore point \"foo\"".to_string(),
+        },
+      ],
+      history.entries
+    );
+    assert_eq!(Some(Lsn(0x5000000)), history.switchpoint(2));
+    assert_eq!(None, history.switchpoint(3));
+    assert!(TimelineHistory::parse(3, "".to_string(), "garbage").is_err());
+  }
+}
@@ -0,0 +1,289 @@
+use std::{fmt, io};
+
+use super::{
+  conn::{escape_literal, quote_identifier, Connection, ConnectionOptions, ReplicationMode, ServerVersion},
+  copy::{CopyFormat, CopyOut},
+  error::ProtocolError,
+  query::{CreateReplicationSlot, Format, QueryResult},
+  types::Value,
+  wal::{OutputPlugin, ReplicationStream, WalCursor},
+};
+
+// Fully qualified name of a table, displayed as a quoted identifier.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableName {
+  pub schema: String,
+  pub name: String,
+}
+
+impl TableName {
+  pub fn new(schema: impl Into<String>, name: impl Into<String>) -> Self {
+    Self {
+      schema: schema.into(),
+      name: name.into(),
+    }
+  }
+}
+
+impl fmt::Display for TableName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}", quote_identifier(&self.schema), quote_identifier(&self.name))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotColumn {
+  pub name: String,
+  pub type_oid: i32,
+  pub type_modifier: i32,
+  pub is_nullable: bool,
+  // Whether the column is part of the primary key.
+  pub is_key: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotTable {
+  pub name: TableName,
+  pub columns: Vec<SnapshotColumn>,
+}
+
+// Consistent initial snapshot of a database, taken when a logical replication slot is created.
+//
+// The slot exports a snapshot that other connections import with `SET TRANSACTION SNAPSHOT`. The exported snapshot is
+// only valid until the replication connection runs its next command, so the connection stays idle until every worker
+// has been opened. Changes committed after the snapshot are then streamed from the slot's consistent point, which
+// means that no change is lost or read twice.
+//
+// https://www.postgresql.org/docs/current/protocol-replication.html
+// https://www.postgresql.org/docs/current/sql-set-transaction.html
+#[derive(Debug)]
+pub struct Snapshot {
+  conn: Connection,
+  slot: CreateReplicationSlot,
+  snapshot_name: String,
+  timeline: u32,
+  output_plugin: OutputPlugin,
+}
+
+impl Snapshot {
+  pub async fn create(mut conn: Connection, slot: impl AsRef<str>, output_plugin: OutputPlugin) -> io::Result<Self> {
+    // IDENTIFY_SYSTEM must run before the slot is created, as any command invalidates the exported snapshot.
+    let timeline = conn.identify_system().await?.timeline;
+    let slot = conn.create_replication_slot_with_snapshot(slot, &output_plugin).await?;
+    let snapshot_name = slot.snapshot_name.clone().ok_or_else(|| {
+      io::Error::new(
+        io::ErrorKind::InvalidData,
+        "replication slot was created without an exported snapshot",
+      )
+    })?;
+
+    Ok(Self {
+      conn,
+      slot,
+      snapshot_name,
+      timeline,
+      output_plugin,
+    })
+  }
+
+  pub fn slot_name(&self) -> &str {
+    &self.slot.slot_name
+  }
+
+  pub fn snapshot_name(&self) -> &str {
+    &self.snapshot_name
+  }
+
+  pub fn output_plugin(&self) -> &OutputPlugin {
+    &self.output_plugin
+  }
+
+  // Position from which changes made after the snapshot are streamed.
+  pub fn wal_cursor(&self) -> WalCursor {
+    WalCursor {
+      timeline: self.timeline,
+      lsn: self.slot.consistent_point,
+    }
+  }
+
+  // Opens a regular connection whose transaction sees the database as of the snapshot. Multiple workers can read
+  // tables in parallel, and they must all be opened before `start_replication_stream` is called.
+  pub async fn open_worker(&self) -> io::Result<SnapshotWorker> {
+    let options = ConnectionOptions {
+      replication: ReplicationMode::Off,
+      ..self.conn.options().clone()
+    };
+    let mut conn = self.conn.duplicate_with_options(options).await?;
+    execute(&mut conn, "BEGIN TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY").await?;
+    execute(
+      &mut conn,
+      format!("SET TRANSACTION SNAPSHOT {}", escape_literal(&self.snapshot_name)),
+    )
+    .await?;
+    Ok(SnapshotWorker { conn })
+  }
+
+  // Streams the changes committed after the snapshot. This releases the exported snapshot, workers that are already
+  // opened keep reading from it.
+  pub async fn start_replication_stream(self) -> io::Result<ReplicationStream> {
+    let wal_cursor = self.wal_cursor();
+    self
+      .conn
+      .start_replication_stream(self.slot.slot_name, wal_cursor, self.output_plugin)
+      .await
+  }
+}
+
+// Connection reading tables within the snapshot's transaction.
+#[derive(Debug)]
+pub struct SnapshotWorker {
+  conn: Connection,
+}
+
+impl SnapshotWorker {
+  // Lists the user tables, excluding the system schemas.
+  pub async fn list_tables(&mut self) -> io::Result<Vec<TableName>> {
+    let rows = self
+      .conn
+      .query_params(
+        "SELECT n.nspname, c.relname
+          FROM pg_catalog.pg_class c
+          JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+          WHERE c.relkind = 'r'
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname NOT LIKE 'pg_toast%'
+          ORDER BY n.nspname, c.relname",
+        &[],
+        Format::Binary,
+      )
+      .await?;
+
+    Ok(
+      rows
+        .map_rows::<(String, String)>()?
+        .into_iter()
+        .map(|(schema, name)| TableName { schema, name })
+        .collect(),
+    )
+  }
+
+  // Columns of the table, except the generated ones: COPY rejects them and pgoutput does not stream them. They only
+  // exist since PostgreSQL 12.
+  pub async fn describe_table(&mut self, name: &TableName) -> io::Result<SnapshotTable> {
+    let not_generated = match self.conn.server_version() {
+      Some(version) if version < ServerVersion::new(12, 0) => "",
+      _ => " AND a.attgenerated = ''",
+    };
+    let rows = self
+      .conn
+      .query_params(
+        format!(
+          "SELECT a.attname, a.atttypid::int4, a.atttypmod, NOT a.attnotnull, COALESCE(a.attnum = ANY(i.indkey), false)
+            FROM pg_catalog.pg_attribute a
+            JOIN pg_catalog.pg_class c ON c.oid = a.attrelid
+            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+            LEFT JOIN pg_catalog.pg_index i ON i.indrelid = c.oid AND i.indisprimary
+            WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped{}
+            ORDER BY a.attnum",
+          not_generated
+        ),
+        &[name.schema.as_str().into(), name.name.as_str().into()],
+        Format::Binary,
+      )
+      .await?;
+
+    let columns = rows
+      .map_rows::<(String, i32, i32, bool, bool)>()?
+      .into_iter()
+      .map(|(name, type_oid, type_modifier, is_nullable, is_key)| SnapshotColumn {
+        name,
+        type_oid,
+        type_modifier,
+        is_nullable,
+        is_key,
+      })
+      .collect::<Vec<_>>();
+
+    if columns.is_empty() {
+      return Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("table {} does not exist or has no columns", name),
+      ));
+    }
+
+    Ok(SnapshotTable {
+      name: name.clone(),
+      columns,
+    })
+  }
+
+  // Reads every row of the table, as of the snapshot.
+  pub async fn read_table<'a>(&'a mut self, table: &'a SnapshotTable) -> io::Result<SnapshotReader<'a>> {
+    let columns = table
+      .columns
+      .iter()
+      .map(|c| quote_identifier(&c.name))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let copy = self
+      .conn
+      .copy_out(format!("COPY {} ({}) TO STDOUT", table.name, columns), CopyFormat::Text)
+      .await?;
+    Ok(SnapshotReader { table, copy })
+  }
+
+  // Ends the snapshot's transaction and closes the connection.
+  pub async fn finish(mut self) -> io::Result<()> {
+    execute(&mut self.conn, "COMMIT").await?;
+    self.conn.close().await
+  }
+}
+
+// Rows of a table read within a snapshot.
+#[derive(Debug)]
+pub struct SnapshotReader<'a> {
+  table: &'a SnapshotTable,
+  copy: CopyOut<'a>,
+}
+
+impl<'a> SnapshotReader<'a> {
+  pub fn table(&self) -> &SnapshotTable {
+    self.table
+  }
+
+  // Next row, with one value per column of the table.
+  pub async fn recv(&mut self) -> Option<io::Result<Vec<Value>>> {
+    let row = match self.copy.recv_row().await? {
+      Ok(row) => row,
+      Err(err) => return Some(Err(err)),
+    };
+    // The COPY names every column of the table, this guards against malformed COPY data.
+    if row.len() != self.table.columns.len() {
+      return Some(Err(
+        ProtocolError::invalid(format!(
+          "{} row has {} values, expected {}",
+          self.table.name,
+          row.len(),
+          self.table.columns.len()
+        ))
+        .into(),
+      ));
+    }
+    Some(
+      self
+        .table
+        .columns
+        .iter()
+        .zip(row.iter())
+        .map(|(column, raw)| Value::decode(column.type_oid, Format::Text, raw.as_ref()))
+        .collect(),
+    )
+  }
+}
+
+async fn execute(conn: &mut Connection, query: impl AsRef<str>) -> io::Result<()> {
+  match conn.query_first(query).await? {
+    QueryResult::BackendError(err) => Err(err),
+    _ => Ok(()),
+  }
+}
@@ -15,11 +15,20 @@ use tokio::{
 
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+use super::error::ProtocolError;
+
+#[cfg(feature = "ssl")]
+use super::conn::SslMode;
+
 #[cfg(feature = "ssl")]
 use tokio_openssl::SslStream;
 
 #[cfg(feature = "ssl")]
-use openssl::ssl::SslConnector;
+use openssl::{
+  hash::MessageDigest,
+  nid::Nid,
+  ssl::{SslConnector, SslVerifyMode},
+};
 
 const SSL_HANDSHAKE_CODE: i32 = 80877103;
 
@@ -30,7 +39,15 @@ pub enum Stream {
   Unix((BufStream<UnixStream>, PathBuf)),
 
   #[cfg(feature = "ssl")]
-  Ssl((SslStream<BufStream<TcpStream>>, Vec<SocketAddr>, String, SslConnector)),
+  Ssl(
+    (
+      SslStream<BufStream<TcpStream>>,
+      Vec<SocketAddr>,
+      String,
+      SslConnector,
+      SslMode,
+    ),
+  ),
 }
 
 impl Stream {
@@ -46,11 +63,14 @@ impl Stream {
     Ok(Self::Unix((s, path)))
   }
 
+  // Negotiates TLS, and verifies the server certificate according to the mode. With `SslMode::Prefer`, the connection
+  // continues unencrypted when the server does not support TLS.
   #[cfg(feature = "ssl")]
   pub async fn connect_ssl(
     addrs: impl Into<Vec<SocketAddr>>,
     domain: impl Into<String>,
     ssl_connector: SslConnector,
+    ssl_mode: SslMode,
   ) -> io::Result<Self> {
     let addrs = addrs.into();
     let domain = domain.into();
@@ -63,10 +83,19 @@ impl Stream {
     match s.read_u8().await? {
       b'S' => {
         // https://www.postgresql.org/docs/11/protocol-flow.html#id-1.10.5.7.11
-        let connect_configuration = ssl_connector
+        let mut connect_configuration = ssl_connector
           .configure()
           .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to create SSL configuration"))?;
 
+        match ssl_mode {
+          SslMode::Disable | SslMode::Allow | SslMode::Prefer | SslMode::Require => {
+            connect_configuration.set_verify(SslVerifyMode::NONE);
+            connect_configuration.set_verify_hostname(false);
+          }
+          SslMode::VerifyCa => connect_configuration.set_verify_hostname(false),
+          SslMode::VerifyFull => {}
+        }
+
         let ssl = connect_configuration
           .into_ssl(domain.as_str())
           .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to create SSL context"))?;
@@ -77,25 +106,66 @@ impl Stream {
         Pin::new(&mut ssl_stream)
           .connect()
           .await
-          .map_err(|err| io::Error::new(io::ErrorKind::ConnectionRefused, err.to_string()))?;
+          .map_err(|err| io::Error::new(io::ErrorKind::ConnectionRefused, err))?;
 
-        Ok(Self::Ssl((ssl_stream, addrs, domain, ssl_connector)))
+        Ok(Self::Ssl((ssl_stream, addrs, domain, ssl_connector, ssl_mode)))
       }
+      // The startup continues on the same connection.
+      b'N' if ssl_mode == SslMode::Prefer => Ok(Self::Tcp((s, addrs))),
       b'N' => Err(io::Error::new(io::ErrorKind::ConnectionReset, "SSL not available")),
-      code => {
-        panic!("Unexpected backend message: {:?}", char::from(code))
+      code => Err(ProtocolError::UnexpectedMessage(code).into()),
+    }
+  }
+
+  // Whether the error comes from a failed TLS handshake in `connect_ssl`, e.g. the server and the client have no
+  // protocol version or cipher in common.
+  #[cfg(feature = "ssl")]
+  pub fn is_ssl_handshake_error(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::ConnectionRefused && err.get_ref().is_some_and(|err| err.is::<openssl::ssl::Error>())
+  }
+
+  // tls-server-end-point channel binding data: hash of the server certificate, computed with the hash function of the
+  // certificate's signature algorithm. MD5 and SHA-1 are replaced by SHA-256. `None` when the stream is not encrypted.
+  //
+  // https://datatracker.ietf.org/doc/html/rfc5929#section-4.1
+  pub fn tls_server_end_point(&self) -> io::Result<Option<Vec<u8>>> {
+    match self {
+      Stream::Tcp(_) | Stream::Unix(_) => Ok(None),
+      #[cfg(feature = "ssl")]
+      Stream::Ssl((s, _, _, _, _)) => {
+        let certificate = s
+          .ssl()
+          .peer_certificate()
+          .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "server did not present a certificate"))?;
+
+        let signature_algorithm = certificate.signature_algorithm().object().nid();
+        let digest = signature_algorithm
+          .signature_algorithms()
+          .map(|algorithms| algorithms.digest)
+          .and_then(|digest| match digest {
+            Nid::MD5 | Nid::SHA1 => Some(MessageDigest::sha256()),
+            digest => MessageDigest::from_nid(digest),
+          })
+          .ok_or_else(|| {
+            io::Error::new(
+              io::ErrorKind::Unsupported,
+              format!(
+                "unsupported certificate signature algorithm {}",
+                signature_algorithm.long_name().unwrap_or("unknown")
+              ),
+            )
+          })?;
+
+        let hash = certificate
+          .digest(digest)
+          .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(Some(hash.to_vec()))
       }
     }
   }
 
   pub async fn read_packet(&mut self) -> io::Result<(u8, Bytes)> {
-    let op = self.read_u8().await?;
-    let len = (self.read_i32().await? - 4).try_into().unwrap();
-    let mut buffer = BytesMut::with_capacity(len);
-    if len > 0 {
-      self.read_buf(&mut buffer).await?;
-    }
-    Ok((op, buffer.freeze()))
+    read_packet(self).await
   }
 
   pub async fn read_packet_with_timeout(&mut self, duration: Option<Duration>) -> io::Result<(u8, Bytes)> {
@@ -123,8 +193,8 @@ impl Stream {
       Stream::Tcp((_, addrs)) => Self::connect_tcp(addrs.clone()).await,
       Stream::Unix((_, path)) => Self::connect_unix(path.clone()).await,
       #[cfg(feature = "ssl")]
-      Stream::Ssl((_, addrs, domain, ssl_connector)) => {
-        Self::connect_ssl(addrs.clone(), domain.clone(), ssl_connector.clone()).await
+      Stream::Ssl((_, addrs, domain, ssl_connector, ssl_mode)) => {
+        Self::connect_ssl(addrs.clone(), domain.clone(), ssl_connector.clone(), *ssl_mode).await
       }
     }
   }
@@ -140,13 +210,36 @@ impl Stream {
   }
 }
 
+// Initial capacity of the buffer of a message. Larger messages grow it as their data arrives, rather than trusting a
+// possibly bogus length with a single allocation.
+const MAX_PREALLOCATED_LEN: usize = 64 * 1024;
+
+// Reads a backend message from any reader, e.g. the reading half of a split stream.
+pub async fn read_packet<R: AsyncRead + Unpin>(r: &mut R) -> io::Result<(u8, Bytes)> {
+  let op = r.read_u8().await?;
+  let len = r.read_i32().await?;
+  let len = len
+    .checked_sub(4)
+    .and_then(|len| usize::try_from(len).ok())
+    .ok_or_else(|| ProtocolError::invalid(format!("invalid length {}", len)))?;
+  let mut buffer = BytesMut::with_capacity(len.min(MAX_PREALLOCATED_LEN));
+  let mut r = r.take(len as u64);
+  while buffer.len() < len {
+    buffer.reserve((len - buffer.len()).min(MAX_PREALLOCATED_LEN));
+    if r.read_buf(&mut buffer).await? == 0 {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+  }
+  Ok((op, buffer.freeze()))
+}
+
 impl AsyncRead for Stream {
   fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
     match self.get_mut() {
       Stream::Tcp((s, _)) => Pin::new(s).poll_read(cx, buf),
       Stream::Unix((s, _)) => Pin::new(s).poll_read(cx, buf),
       #[cfg(feature = "ssl")]
-      Stream::Ssl((s, _, _, _)) => Pin::new(s).poll_read(cx, buf),
+      Stream::Ssl((s, _, _, _, _)) => Pin::new(s).poll_read(cx, buf),
     }
   }
 }
@@ -157,7 +250,7 @@ impl AsyncWrite for Stream {
       Stream::Tcp((s, _)) => Pin::new(s).poll_write(cx, buf),
       Stream::Unix((s, _)) => Pin::new(s).poll_write(cx, buf),
       #[cfg(feature = "ssl")]
-      Stream::Ssl((s, _, _, _)) => Pin::new(s).poll_write(cx, buf),
+      Stream::Ssl((s, _, _, _, _)) => Pin::new(s).poll_write(cx, buf),
     }
   }
 
@@ -166,7 +259,7 @@ impl AsyncWrite for Stream {
       Stream::Tcp((s, _)) => Pin::new(s).poll_flush(cx),
       Stream::Unix((s, _)) => Pin::new(s).poll_flush(cx),
       #[cfg(feature = "ssl")]
-      Stream::Ssl((s, _, _, _)) => Pin::new(s).poll_flush(cx),
+      Stream::Ssl((s, _, _, _, _)) => Pin::new(s).poll_flush(cx),
     }
   }
 
@@ -175,7 +268,38 @@ impl AsyncWrite for Stream {
       Stream::Tcp((s, _)) => Pin::new(s).poll_shutdown(cx),
       Stream::Unix((s, _)) => Pin::new(s).poll_shutdown(cx),
       #[cfg(feature = "ssl")]
-      Stream::Ssl((s, _, _, _)) => Pin::new(s).poll_shutdown(cx),
+      Stream::Ssl((s, _, _, _, _)) => Pin::new(s).poll_shutdown(cx),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[tokio::test]
+  async fn reads_packets() {
+    let mut r: &[u8] = &[b'Z', 0, 0, 0, 5, b'I', b'C', 0, 0, 0, 4];
+    assert_eq!((b'Z', Bytes::from_static(b"I")), read_packet(&mut r).await.unwrap());
+    assert_eq!((b'C', Bytes::new()), read_packet(&mut r).await.unwrap());
+    assert!(r.is_empty());
+  }
+
+  #[tokio::test]
+  async fn rejects_invalid_lengths() {
+    for len in [3, 0, -1, i32::MIN] {
+      let mut packet = vec![b'D'];
+      packet.extend_from_slice(&len.to_be_bytes());
+      let err = read_packet(&mut packet.as_slice()).await.unwrap_err();
+      assert_eq!(
+        Some(&ProtocolError::invalid(format!("invalid length {}", len))),
+        ProtocolError::from_io_error(&err)
+      );
     }
+
+    // Truncated message, e.g. a bogus length.
+    let mut r: &[u8] = &[b'D', 0x7f, 0xff, 0xff, 0xff, 0, 1];
+    let err = read_packet(&mut r).await.unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
   }
 }
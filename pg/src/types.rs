@@ -0,0 +1,938 @@
+use std::{fmt::Write, io};
+
+use bytes::{Buf, Bytes};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use uuid::Uuid;
+
+use super::error::ProtocolError;
+use super::query::{Column, Format, Rows, SelectQueryResult};
+
+// https://github.com/postgres/postgres/blob/master/src/include/catalog/pg_type.dat
+pub mod oid {
+  pub const BOOL: i32 = 16;
+  pub const BYTEA: i32 = 17;
+  pub const CHAR: i32 = 18;
+  pub const NAME: i32 = 19;
+  pub const INT8: i32 = 20;
+  pub const INT2: i32 = 21;
+  pub const INT4: i32 = 23;
+  pub const TEXT: i32 = 25;
+  pub const OID: i32 = 26;
+  pub const JSON: i32 = 114;
+  pub const FLOAT4: i32 = 700;
+  pub const FLOAT8: i32 = 701;
+  pub const BPCHAR: i32 = 1042;
+  pub const VARCHAR: i32 = 1043;
+  pub const DATE: i32 = 1082;
+  pub const TIME: i32 = 1083;
+  pub const TIMESTAMP: i32 = 1114;
+  pub const TIMESTAMPTZ: i32 = 1184;
+  pub const INTERVAL: i32 = 1186;
+  pub const NUMERIC: i32 = 1700;
+  pub const UUID: i32 = 2950;
+  pub const JSONB: i32 = 3802;
+
+  pub const JSON_ARRAY: i32 = 199;
+  pub const BOOL_ARRAY: i32 = 1000;
+  pub const BYTEA_ARRAY: i32 = 1001;
+  pub const CHAR_ARRAY: i32 = 1002;
+  pub const NAME_ARRAY: i32 = 1003;
+  pub const INT2_ARRAY: i32 = 1005;
+  pub const INT4_ARRAY: i32 = 1007;
+  pub const TEXT_ARRAY: i32 = 1009;
+  pub const BPCHAR_ARRAY: i32 = 1014;
+  pub const VARCHAR_ARRAY: i32 = 1015;
+  pub const INT8_ARRAY: i32 = 1016;
+  pub const FLOAT4_ARRAY: i32 = 1021;
+  pub const FLOAT8_ARRAY: i32 = 1022;
+  pub const OID_ARRAY: i32 = 1028;
+  pub const TIMESTAMP_ARRAY: i32 = 1115;
+  pub const DATE_ARRAY: i32 = 1182;
+  pub const TIME_ARRAY: i32 = 1183;
+  pub const TIMESTAMPTZ_ARRAY: i32 = 1185;
+  pub const INTERVAL_ARRAY: i32 = 1187;
+  pub const NUMERIC_ARRAY: i32 = 1231;
+  pub const UUID_ARRAY: i32 = 2951;
+  pub const JSONB_ARRAY: i32 = 3807;
+
+  // Element type of the builtin array types above.
+  pub fn array_element(array_oid: i32) -> Option<i32> {
+    match array_oid {
+      JSON_ARRAY => Some(JSON),
+      BOOL_ARRAY => Some(BOOL),
+      BYTEA_ARRAY => Some(BYTEA),
+      CHAR_ARRAY => Some(CHAR),
+      NAME_ARRAY => Some(NAME),
+      INT2_ARRAY => Some(INT2),
+      INT4_ARRAY => Some(INT4),
+      TEXT_ARRAY => Some(TEXT),
+      BPCHAR_ARRAY => Some(BPCHAR),
+      VARCHAR_ARRAY => Some(VARCHAR),
+      INT8_ARRAY => Some(INT8),
+      FLOAT4_ARRAY => Some(FLOAT4),
+      FLOAT8_ARRAY => Some(FLOAT8),
+      OID_ARRAY => Some(OID),
+      TIMESTAMP_ARRAY => Some(TIMESTAMP),
+      DATE_ARRAY => Some(DATE),
+      TIME_ARRAY => Some(TIME),
+      TIMESTAMPTZ_ARRAY => Some(TIMESTAMPTZ),
+      INTERVAL_ARRAY => Some(INTERVAL),
+      NUMERIC_ARRAY => Some(NUMERIC),
+      UUID_ARRAY => Some(UUID),
+      JSONB_ARRAY => Some(JSONB),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+  pub months: i32,
+  pub days: i32,
+  pub microseconds: i64,
+}
+
+// Value of a column decoded according to its type oid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  Int2(i16),
+  Int4(i32),
+  Int8(i64),
+  Oid(u32),
+  Float4(f32),
+  Float8(f64),
+  // Kept as text to avoid losing precision, e.g. `-12.3400`, `NaN`.
+  Numeric(String),
+  Text(String),
+  Bytea(Bytes),
+  Uuid(Uuid),
+  Json(serde_json::Value),
+  Date(NaiveDate),
+  Time(NaiveTime),
+  Timestamp(NaiveDateTime),
+  TimestampTz(DateTime<Utc>),
+  Interval(Interval),
+  // Multi-dimensional arrays are nested.
+  Array(Vec<Value>),
+  // Value of a type that is not supported, as sent by the server.
+  Unknown { type_oid: i32, raw: Bytes },
+}
+
+fn invalid_value(msg: impl Into<String>) -> io::Error {
+  ProtocolError::invalid(msg).into()
+}
+
+impl Value {
+  pub fn decode(type_oid: i32, format: Format, raw: Option<&Bytes>) -> io::Result<Self> {
+    match (raw, format) {
+      (None, _) => Ok(Self::Null),
+      (Some(raw), Format::Text) => {
+        let text = std::str::from_utf8(raw).map_err(|_| io::Error::from(ProtocolError::InvalidUtf8))?;
+        Self::decode_text(type_oid, text).map(|v| match v {
+          Self::Unknown { type_oid, .. } => Self::Unknown {
+            type_oid,
+            raw: raw.clone(),
+          },
+          v => v,
+        })
+      }
+      (Some(raw), Format::Binary) => Self::decode_binary(type_oid, raw.clone()),
+    }
+  }
+
+  pub fn decode_text(type_oid: i32, text: &str) -> io::Result<Self> {
+    fn parse<T: std::str::FromStr>(text: &str) -> io::Result<T>
+    where
+      T::Err: std::fmt::Display,
+    {
+      text
+        .parse()
+        .map_err(|err: T::Err| invalid_value(format!("{}: {:?}", err, text)))
+    }
+
+    fn chrono<T>(v: chrono::ParseResult<T>, text: &str) -> io::Result<T> {
+      v.map_err(|err| invalid_value(format!("{}: {:?}", err, text)))
+    }
+
+    match type_oid {
+      oid::BOOL => match text {
+        "t" => Ok(Self::Bool(true)),
+        "f" => Ok(Self::Bool(false)),
+        _ => Err(invalid_value(format!("invalid bool: {:?}", text))),
+      },
+      oid::INT2 => parse(text).map(Self::Int2),
+      oid::INT4 => parse(text).map(Self::Int4),
+      oid::INT8 => parse(text).map(Self::Int8),
+      oid::OID => parse(text).map(Self::Oid),
+      oid::FLOAT4 => parse(text).map(Self::Float4),
+      oid::FLOAT8 => parse(text).map(Self::Float8),
+      oid::NUMERIC => Ok(Self::Numeric(text.to_string())),
+      oid::TEXT | oid::VARCHAR | oid::BPCHAR | oid::NAME | oid::CHAR => Ok(Self::Text(text.to_string())),
+      oid::BYTEA => decode_text_bytea(text).map(Self::Bytea),
+      oid::UUID => parse(text).map(Self::Uuid),
+      oid::JSON | oid::JSONB => serde_json::from_str(text)
+        .map(Self::Json)
+        .map_err(|err| invalid_value(err.to_string())),
+      oid::DATE => chrono(NaiveDate::parse_from_str(text, "%Y-%m-%d"), text).map(Self::Date),
+      oid::TIME => chrono(NaiveTime::parse_from_str(text, "%H:%M:%S%.f"), text).map(Self::Time),
+      oid::TIMESTAMP => chrono(NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f"), text).map(Self::Timestamp),
+      oid::TIMESTAMPTZ => chrono(DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z"), text)
+        .map(|v| Self::TimestampTz(v.with_timezone(&Utc))),
+      oid::INTERVAL => decode_text_interval(text).map(Self::Interval),
+      type_oid => match oid::array_element(type_oid) {
+        Some(element_oid) => decode_text_array(element_oid, text),
+        None => Ok(Self::Unknown {
+          type_oid,
+          raw: Bytes::copy_from_slice(text.as_bytes()),
+        }),
+      },
+    }
+  }
+
+  pub fn decode_binary(type_oid: i32, mut raw: Bytes) -> io::Result<Self> {
+    fn expect_len(raw: &Bytes, len: usize) -> io::Result<()> {
+      if raw.len() == len {
+        Ok(())
+      } else {
+        Err(invalid_value(format!("expected {} bytes, got {}", len, raw.len())))
+      }
+    }
+
+    fn utf8(raw: Bytes) -> io::Result<String> {
+      String::from_utf8(raw.to_vec()).map_err(|_| ProtocolError::InvalidUtf8.into())
+    }
+
+    match type_oid {
+      oid::BOOL => expect_len(&raw, 1).map(|_| Self::Bool(raw[0] != 0)),
+      oid::INT2 => expect_len(&raw, 2).map(|_| Self::Int2(raw.get_i16())),
+      oid::INT4 => expect_len(&raw, 4).map(|_| Self::Int4(raw.get_i32())),
+      oid::INT8 => expect_len(&raw, 8).map(|_| Self::Int8(raw.get_i64())),
+      oid::OID => expect_len(&raw, 4).map(|_| Self::Oid(raw.get_u32())),
+      oid::FLOAT4 => expect_len(&raw, 4).map(|_| Self::Float4(raw.get_f32())),
+      oid::FLOAT8 => expect_len(&raw, 8).map(|_| Self::Float8(raw.get_f64())),
+      oid::NUMERIC => decode_binary_numeric(raw).map(Self::Numeric),
+      oid::TEXT | oid::VARCHAR | oid::BPCHAR | oid::NAME | oid::CHAR => utf8(raw).map(Self::Text),
+      oid::BYTEA => Ok(Self::Bytea(raw)),
+      oid::UUID => Uuid::from_slice(&raw)
+        .map(Self::Uuid)
+        .map_err(|err| invalid_value(err.to_string())),
+      oid::JSON => serde_json::from_slice(&raw)
+        .map(Self::Json)
+        .map_err(|err| invalid_value(err.to_string())),
+      oid::JSONB => {
+        // jsonb is sent as a version number (currently 1) followed by the json text.
+        match raw.first() {
+          Some(1) => serde_json::from_slice(&raw[1..])
+            .map(Self::Json)
+            .map_err(|err| invalid_value(err.to_string())),
+          version => Err(invalid_value(format!("unsupported jsonb version: {:?}", version))),
+        }
+      }
+      oid::DATE => {
+        expect_len(&raw, 4)?;
+        pg_epoch()
+          .date()
+          .checked_add_signed(Duration::days(raw.get_i32().into()))
+          .map(Self::Date)
+          .ok_or_else(|| invalid_value("date out of range"))
+      }
+      oid::TIME => {
+        expect_len(&raw, 8)?;
+        let micros = raw.get_i64();
+        NaiveTime::from_num_seconds_from_midnight_opt((micros / 1_000_000) as u32, (micros % 1_000_000 * 1_000) as u32)
+          .map(Self::Time)
+          .ok_or_else(|| invalid_value("time out of range"))
+      }
+      oid::TIMESTAMP => {
+        expect_len(&raw, 8)?;
+        decode_binary_timestamp(raw.get_i64()).map(Self::Timestamp)
+      }
+      oid::TIMESTAMPTZ => {
+        expect_len(&raw, 8)?;
+        decode_binary_timestamp(raw.get_i64()).map(|v| Self::TimestampTz(DateTime::from_utc(v, Utc)))
+      }
+      oid::INTERVAL => {
+        expect_len(&raw, 16)?;
+        let microseconds = raw.get_i64();
+        let days = raw.get_i32();
+        let months = raw.get_i32();
+        Ok(Self::Interval(Interval {
+          months,
+          days,
+          microseconds,
+        }))
+      }
+      type_oid if oid::array_element(type_oid).is_some() => decode_binary_array(raw),
+      type_oid => Ok(Self::Unknown { type_oid, raw }),
+    }
+  }
+
+  pub fn is_null(&self) -> bool {
+    matches!(self, Self::Null)
+  }
+}
+
+fn pg_epoch() -> NaiveDateTime {
+  NaiveDate::from_ymd_opt(2000, 1, 1)
+    .and_then(|d| d.and_hms_opt(0, 0, 0))
+    .unwrap()
+}
+
+fn decode_binary_timestamp(micros: i64) -> io::Result<NaiveDateTime> {
+  if micros == i64::MAX || micros == i64::MIN {
+    return Err(invalid_value("infinite timestamps are not supported"));
+  }
+  pg_epoch()
+    .checked_add_signed(Duration::microseconds(micros))
+    .ok_or_else(|| invalid_value("timestamp out of range"))
+}
+
+fn decode_text_bytea(text: &str) -> io::Result<Bytes> {
+  match text.strip_prefix("\\x") {
+    // hex format (default since 9.0)
+    Some(hex) => {
+      if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(invalid_value("invalid bytea hex length"));
+      }
+      (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|err| invalid_value(err.to_string())))
+        .collect::<io::Result<Vec<_>>>()
+        .map(Bytes::from)
+    }
+    // escape format
+    None => {
+      let bytes = text.as_bytes();
+      let mut v = Vec::with_capacity(bytes.len());
+      let mut i = 0;
+      while i < bytes.len() {
+        match bytes[i] {
+          b'\\' if bytes.get(i + 1) == Some(&b'\\') => {
+            v.push(b'\\');
+            i += 2;
+          }
+          b'\\' if bytes.len() >= i + 4 => {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).map_err(|err| invalid_value(err.to_string()))?;
+            v.push(u8::from_str_radix(octal, 8).map_err(|err| invalid_value(err.to_string()))?);
+            i += 4;
+          }
+          b'\\' => return Err(invalid_value("invalid bytea escape sequence")),
+          b => {
+            v.push(b);
+            i += 1;
+          }
+        }
+      }
+      Ok(Bytes::from(v))
+    }
+  }
+}
+
+// Parses intervals formatted with the default `postgres` IntervalStyle, e.g. `1 year 2 mons -3 days +04:05:06.5`.
+fn decode_text_interval(text: &str) -> io::Result<Interval> {
+  let err = || invalid_value(format!("invalid interval: {:?}", text));
+  let mut interval = Interval::default();
+  let mut tokens = text.split_whitespace();
+
+  while let Some(token) = tokens.next() {
+    if token.contains(':') {
+      let (sign, time) = match token.as_bytes()[0] {
+        b'-' => (-1, &token[1..]),
+        b'+' => (1, &token[1..]),
+        _ => (1, token),
+      };
+      let mut parts = time.splitn(3, ':');
+      // Parsed as i128 so that the conversion into microseconds cannot overflow.
+      let hours: i128 = parts.next().and_then(|v| v.parse::<i64>().ok()).ok_or_else(err)?.into();
+      let minutes: i128 = parts.next().and_then(|v| v.parse::<i64>().ok()).ok_or_else(err)?.into();
+      let (seconds, fraction) = match parts.next() {
+        Some(v) => v.split_once('.').unwrap_or((v, "")),
+        None => ("0", ""),
+      };
+      let seconds: i128 = seconds.parse::<i64>().map_err(|_| err())?.into();
+      let micros: i128 = if fraction.is_empty() {
+        0
+      } else {
+        format!("{:0<6}", fraction.get(..fraction.len().min(6)).ok_or_else(err)?)
+          .parse()
+          .map_err(|_| err())?
+      };
+      let microseconds = sign * (((hours * 60 + minutes) * 60 + seconds) * 1_000_000 + micros);
+      interval.microseconds = i64::try_from(i128::from(interval.microseconds) + microseconds).map_err(|_| err())?;
+    } else {
+      let n: i32 = token.parse().map_err(|_| err())?;
+      let (field, n) = match tokens.next().ok_or_else(err)? {
+        "year" | "years" => (&mut interval.months, n.checked_mul(12).ok_or_else(err)?),
+        "mon" | "mons" => (&mut interval.months, n),
+        "day" | "days" => (&mut interval.days, n),
+        _ => return Err(err()),
+      };
+      *field = field.checked_add(n).ok_or_else(err)?;
+    }
+  }
+
+  Ok(interval)
+}
+
+fn decode_binary_numeric(mut raw: Bytes) -> io::Result<String> {
+  // https://github.com/postgres/postgres/blob/master/src/backend/utils/adt/numeric.c (numeric_send)
+  if raw.len() < 8 {
+    return Err(ProtocolError::UnexpectedEof.into());
+  }
+  let ndigits = raw.get_i16();
+  let weight = raw.get_i16();
+  let sign = raw.get_u16();
+  let dscale = raw.get_u16();
+
+  if ndigits < 0 || raw.len() != ndigits as usize * 2 {
+    return Err(invalid_value("invalid numeric digits"));
+  }
+  let digits = (0..ndigits).map(|_| raw.get_i16()).collect::<Vec<_>>();
+  let digit = |i: i32| {
+    if i < 0 {
+      0
+    } else {
+      digits.get(i as usize).copied().unwrap_or(0)
+    }
+  };
+
+  let mut s = String::new();
+  match sign {
+    0x0000 => {}
+    0x4000 => s.push('-'),
+    0xC000 => return Ok("NaN".to_string()),
+    0xD000 => return Ok("Infinity".to_string()),
+    0xF000 => return Ok("-Infinity".to_string()),
+    sign => return Err(invalid_value(format!("invalid numeric sign: {:#x}", sign))),
+  }
+
+  let weight = i32::from(weight);
+  if weight < 0 {
+    s.push('0');
+  } else {
+    write!(s, "{}", digit(0)).unwrap();
+    for i in 1..=weight {
+      write!(s, "{:04}", digit(i)).unwrap();
+    }
+  }
+
+  if dscale > 0 {
+    let mut fraction = String::new();
+    let mut i = weight + 1;
+    while fraction.len() < dscale as usize {
+      write!(fraction, "{:04}", digit(i)).unwrap();
+      i += 1;
+    }
+    fraction.truncate(dscale as usize);
+    s.push('.');
+    s.push_str(&fraction);
+  }
+
+  Ok(s)
+}
+
+fn decode_text_array(element_oid: i32, text: &str) -> io::Result<Value> {
+  // Arrays with non default lower bounds are prefixed with their dimensions, e.g. `[0:1]={1,2}`.
+  let text = match text.starts_with('[') {
+    true => text.split_once('=').map(|(_, v)| v).unwrap_or(text),
+    false => text,
+  };
+
+  let mut chars = text.chars().peekable();
+  let value = parse_text_array(element_oid, &mut chars)?;
+  match chars.next() {
+    None => Ok(value),
+    Some(c) => Err(invalid_value(format!("unexpected {:?} after array", c))),
+  }
+}
+
+fn parse_text_array(element_oid: i32, chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> io::Result<Value> {
+  let err = || invalid_value("invalid array literal");
+
+  if chars.next() != Some('{') {
+    return Err(err());
+  }
+
+  let mut values = Vec::new();
+  if chars.peek() == Some(&'}') {
+    chars.next();
+    return Ok(Value::Array(values));
+  }
+
+  loop {
+    match chars.peek().ok_or_else(err)? {
+      '{' => values.push(parse_text_array(element_oid, chars)?),
+      '"' => {
+        chars.next();
+        let mut element = String::new();
+        loop {
+          match chars.next().ok_or_else(err)? {
+            '\\' => element.push(chars.next().ok_or_else(err)?),
+            '"' => break,
+            c => element.push(c),
+          }
+        }
+        values.push(Value::decode_text(element_oid, &element)?);
+      }
+      _ => {
+        let mut element = String::new();
+        while let Some(c) = chars.peek() {
+          if *c == ',' || *c == '}' {
+            break;
+          }
+          element.push(*c);
+          chars.next();
+        }
+        match element.as_str() {
+          "NULL" => values.push(Value::Null),
+          element => values.push(Value::decode_text(element_oid, element)?),
+        }
+      }
+    }
+
+    match chars.next().ok_or_else(err)? {
+      ',' => {}
+      '}' => return Ok(Value::Array(values)),
+      _ => return Err(err()),
+    }
+  }
+}
+
+fn decode_binary_array(mut raw: Bytes) -> io::Result<Value> {
+  // https://github.com/postgres/postgres/blob/master/src/backend/utils/adt/arrayfuncs.c (array_send)
+  if raw.len() < 12 {
+    return Err(ProtocolError::UnexpectedEof.into());
+  }
+  let ndim = raw.get_i32();
+  let _has_nulls = raw.get_i32();
+  let element_oid = raw.get_i32();
+
+  let mut dims = Vec::new();
+  for _i in 0..ndim {
+    if raw.len() < 8 {
+      return Err(ProtocolError::UnexpectedEof.into());
+    }
+    let len = raw.get_i32();
+    let _lower_bound = raw.get_i32();
+    dims.push(usize::try_from(len).map_err(|_| invalid_value("invalid array dimension"))?);
+  }
+
+  let count = match dims.iter().try_fold(1usize, |count, dim| count.checked_mul(*dim)) {
+    _ if dims.is_empty() => 0,
+    Some(count) => count,
+    None => return Err(invalid_value("invalid array dimension")),
+  };
+  if count == 0 {
+    return Ok(Value::Array(vec![]));
+  }
+  // Every element takes at least 4 bytes, which bounds the allocation when the dimensions are bogus.
+  let mut elements = Vec::with_capacity(count.min(raw.len() / 4));
+  for _i in 0..count {
+    if raw.len() < 4 {
+      return Err(ProtocolError::UnexpectedEof.into());
+    }
+    match raw.get_i32() {
+      -1 => elements.push(Value::Null),
+      len => {
+        let len = usize::try_from(len).map_err(|_| invalid_value("invalid array element length"))?;
+        if raw.len() < len {
+          return Err(ProtocolError::UnexpectedEof.into());
+        }
+        elements.push(Value::decode_binary(element_oid, raw.split_to(len))?);
+      }
+    }
+  }
+
+  // Rebuild the nesting, innermost dimension first.
+  for dim in dims.iter().skip(1).rev() {
+    let mut nested = Vec::with_capacity(elements.len() / dim);
+    let mut iter = elements.into_iter();
+    loop {
+      let chunk = iter.by_ref().take(*dim).collect::<Vec<_>>();
+      if chunk.is_empty() {
+        break;
+      }
+      nested.push(Value::Array(chunk));
+    }
+    elements = nested;
+  }
+
+  Ok(Value::Array(elements))
+}
+
+// Conversion from a decoded value into a rust type.
+pub trait FromValue: Sized {
+  fn from_value(value: Value) -> io::Result<Self>;
+}
+
+// Not a protocol error: the value is valid, but was asked as the wrong type.
+fn unexpected_value<T>(value: &Value) -> io::Result<T> {
+  Err(io::Error::new(
+    io::ErrorKind::InvalidData,
+    format!("cannot convert {:?} into {}", value, std::any::type_name::<T>()),
+  ))
+}
+
+macro_rules! impl_from_value {
+  ($t:ty, $($variant:ident)|+) => {
+    impl FromValue for $t {
+      fn from_value(value: Value) -> io::Result<Self> {
+        match value {
+          $(Value::$variant(v) => Ok(v.into()),)+
+          value => unexpected_value(&value),
+        }
+      }
+    }
+  };
+}
+
+impl_from_value!(bool, Bool);
+impl_from_value!(i16, Int2);
+impl_from_value!(i32, Int2 | Int4);
+impl_from_value!(i64, Int2 | Int4 | Int8 | Oid);
+impl_from_value!(u32, Oid);
+impl_from_value!(f32, Float4);
+impl_from_value!(f64, Float4 | Float8);
+impl_from_value!(Bytes, Bytea);
+impl_from_value!(Uuid, Uuid);
+impl_from_value!(serde_json::Value, Json);
+impl_from_value!(NaiveDate, Date);
+impl_from_value!(NaiveTime, Time);
+impl_from_value!(NaiveDateTime, Timestamp);
+impl_from_value!(DateTime<Utc>, TimestampTz);
+impl_from_value!(Interval, Interval);
+
+impl FromValue for String {
+  fn from_value(value: Value) -> io::Result<Self> {
+    match value {
+      Value::Text(v) | Value::Numeric(v) => Ok(v),
+      value => unexpected_value(&value),
+    }
+  }
+}
+
+impl FromValue for Vec<u8> {
+  fn from_value(value: Value) -> io::Result<Self> {
+    match value {
+      Value::Bytea(v) => Ok(v.to_vec()),
+      value => unexpected_value(&value),
+    }
+  }
+}
+
+impl FromValue for Value {
+  fn from_value(value: Value) -> io::Result<Self> {
+    Ok(value)
+  }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+  fn from_value(value: Value) -> io::Result<Self> {
+    match value {
+      Value::Null => Ok(None),
+      value => T::from_value(value).map(Some),
+    }
+  }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+  fn from_value(value: Value) -> io::Result<Self> {
+    match value {
+      Value::Array(values) => values.into_iter().map(T::from_value).collect(),
+      value => unexpected_value(&value),
+    }
+  }
+}
+
+// Column lookup for `Row::get`, either by position or by name.
+pub trait ColumnIndex {
+  fn index(&self, columns: &[Column]) -> Option<usize>;
+}
+
+impl ColumnIndex for usize {
+  fn index(&self, columns: &[Column]) -> Option<usize> {
+    (*self < columns.len()).then_some(*self)
+  }
+}
+
+impl ColumnIndex for &str {
+  fn index(&self, columns: &[Column]) -> Option<usize> {
+    columns.iter().position(|c| c.name == *self)
+  }
+}
+
+// A row with its values decoded according to the column types.
+#[derive(Debug)]
+pub struct Row<'a> {
+  columns: &'a [Column],
+  values: Vec<Value>,
+}
+
+impl<'a> Row<'a> {
+  pub fn decode(columns: &'a [Column], raw: &[Option<Bytes>]) -> io::Result<Self> {
+    let values = columns
+      .iter()
+      .zip(raw)
+      .map(|(column, raw)| {
+        let format = match column.format {
+          1 => Format::Binary,
+          _ => Format::Text,
+        };
+        Value::decode(column.datatype_oid, format, raw.as_ref())
+      })
+      .collect::<io::Result<Vec<_>>>()?;
+    Ok(Self { columns, values })
+  }
+
+  pub fn columns(&self) -> &[Column] {
+    self.columns
+  }
+
+  pub fn values(&self) -> &[Value] {
+    &self.values
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  pub fn get<T: FromValue>(&self, index: impl ColumnIndex) -> io::Result<T> {
+    let i = index
+      .index(self.columns)
+      .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "column not found"))?;
+    T::from_value(self.values[i].clone())
+  }
+}
+
+// Conversion from a decoded row into a rust type, e.g. a struct of catalog columns.
+pub trait FromRow: Sized {
+  fn from_row(row: &Row<'_>) -> io::Result<Self>;
+}
+
+macro_rules! impl_from_row_tuple {
+  ($($t:ident: $i:tt),+) => {
+    impl<$($t: FromValue),+> FromRow for ($($t,)+) {
+      fn from_row(row: &Row<'_>) -> io::Result<Self> {
+        Ok(($(row.get::<$t>($i)?,)+))
+      }
+    }
+  };
+}
+
+impl_from_row_tuple!(A: 0);
+impl_from_row_tuple!(A: 0, B: 1);
+impl_from_row_tuple!(A: 0, B: 1, C: 2);
+impl_from_row_tuple!(A: 0, B: 1, C: 2, D: 3);
+impl_from_row_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_from_row_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+impl_from_row_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+impl_from_row_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
+impl Rows {
+  pub fn decode_rows(&self) -> io::Result<Vec<Row<'_>>> {
+    match self.rows() {
+      Some(rows) => rows.map(|raw| Row::decode(&self.columns, raw)).collect(),
+      None => Ok(Vec::new()),
+    }
+  }
+
+  pub fn map_rows<T: FromRow>(&self) -> io::Result<Vec<T>> {
+    self.decode_rows()?.iter().map(T::from_row).collect()
+  }
+}
+
+impl SelectQueryResult {
+  // Values of the simple query protocol are always in text format.
+  pub fn decode_rows(&self) -> io::Result<Vec<Row<'_>>> {
+    match self.rows() {
+      Some(rows) => rows
+        .map(|raw| {
+          let raw = raw
+            .iter()
+            .map(|v| v.as_ref().map(|v| Bytes::copy_from_slice(v.as_bytes())))
+            .collect::<Vec<_>>();
+          Row::decode(&self.columns, &raw)
+        })
+        .collect(),
+      None => Ok(Vec::new()),
+    }
+  }
+
+  pub fn map_rows<T: FromRow>(&self) -> io::Result<Vec<T>> {
+    self.decode_rows()?.iter().map(T::from_row).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use bytes::Bytes;
+  use chrono::{DateTime, NaiveDate, Utc};
+
+  use super::{decode_binary_numeric, oid, Format, Interval, ProtocolError, Value};
+
+  #[test]
+  fn decodes_text_values() {
+    assert_eq!(Value::Bool(true), Value::decode_text(oid::BOOL, "t").unwrap());
+    assert_eq!(Value::Int8(-42), Value::decode_text(oid::INT8, "-42").unwrap());
+    assert_eq!(Value::Float8(1.5), Value::decode_text(oid::FLOAT8, "1.5").unwrap());
+    assert_eq!(
+      Value::Bytea(Bytes::from_static(b"\xde\xad")),
+      Value::decode_text(oid::BYTEA, "\\xdead").unwrap()
+    );
+    assert_eq!(
+      Value::Bytea(Bytes::from_static(b"a\\\x01")),
+      Value::decode_text(oid::BYTEA, "a\\\\\\001").unwrap()
+    );
+    assert_eq!(
+      Value::Date(NaiveDate::from_ymd_opt(2023, 4, 5).unwrap()),
+      Value::decode_text(oid::DATE, "2023-04-05").unwrap()
+    );
+    assert_eq!(
+      Value::TimestampTz(DateTime::from_utc(
+        NaiveDate::from_ymd_opt(2023, 4, 5)
+          .unwrap()
+          .and_hms_opt(1, 2, 3)
+          .unwrap(),
+        Utc
+      )),
+      Value::decode_text(oid::TIMESTAMPTZ, "2023-04-05 03:32:03+02:30").unwrap()
+    );
+    assert_eq!(
+      Value::TimestampTz(DateTime::from_utc(
+        NaiveDate::from_ymd_opt(2023, 4, 5)
+          .unwrap()
+          .and_hms_opt(1, 2, 3)
+          .unwrap(),
+        Utc
+      )),
+      Value::decode_text(oid::TIMESTAMPTZ, "2023-04-05 01:02:03+00").unwrap()
+    );
+    assert!(Value::decode_text(oid::INT4, "nope").is_err());
+  }
+
+  #[test]
+  fn decodes_text_intervals() {
+    assert_eq!(
+      Value::Interval(Interval {
+        months: 14,
+        days: -3,
+        microseconds: 14_706_500_000,
+      }),
+      Value::decode_text(oid::INTERVAL, "1 year 2 mons -3 days +04:05:06.5").unwrap()
+    );
+    assert_eq!(
+      Value::Interval(Interval {
+        months: 0,
+        days: 0,
+        microseconds: -1_000,
+      }),
+      Value::decode_text(oid::INTERVAL, "-00:00:00.001").unwrap()
+    );
+  }
+
+  #[test]
+  fn decodes_text_arrays() {
+    assert_eq!(
+      Value::Array(vec![
+        Value::Array(vec![Value::Int4(1), Value::Null]),
+        Value::Array(vec![Value::Int4(3), Value::Int4(4)]),
+      ]),
+      Value::decode_text(oid::INT4_ARRAY, "{{1,NULL},{3,4}}").unwrap()
+    );
+    assert_eq!(
+      Value::Array(vec![
+        Value::Text("a b".to_string()),
+        Value::Text("c\"d".to_string()),
+        Value::Text("NULL".to_string()),
+      ]),
+      Value::decode_text(oid::TEXT_ARRAY, r#"{"a b","c\"d","NULL"}"#).unwrap()
+    );
+    assert_eq!(Value::Array(vec![]), Value::decode_text(oid::TEXT_ARRAY, "{}").unwrap());
+    assert_eq!(
+      Value::Array(vec![Value::Int2(1), Value::Int2(2)]),
+      Value::decode_text(oid::INT2_ARRAY, "[0:1]={1,2}").unwrap()
+    );
+  }
+
+  #[test]
+  fn decodes_binary_values() {
+    assert_eq!(
+      Value::Int4(258),
+      Value::decode_binary(oid::INT4, Bytes::from_static(&[0, 0, 1, 2])).unwrap()
+    );
+    assert_eq!(
+      Value::Date(NaiveDate::from_ymd_opt(1999, 12, 31).unwrap()),
+      Value::decode_binary(oid::DATE, Bytes::from_static(&[0xff, 0xff, 0xff, 0xff])).unwrap()
+    );
+    assert_eq!(
+      Value::Json(serde_json::json!({"a": 1})),
+      Value::decode_binary(oid::JSONB, Bytes::from_static(b"\x01{\"a\": 1}")).unwrap()
+    );
+    assert!(Value::decode_binary(oid::INT8, Bytes::from_static(&[0, 1])).is_err());
+  }
+
+  #[test]
+  fn decodes_binary_arrays() {
+    #[rustfmt::skip]
+    let raw = Bytes::from_static(&[
+      0, 0, 0, 2, // ndim
+      0, 0, 0, 1, // has nulls
+      0, 0, 0, 23, // int4
+      0, 0, 0, 2, 0, 0, 0, 1, // dim 1
+      0, 0, 0, 2, 0, 0, 0, 1, // dim 2
+      0, 0, 0, 4, 0, 0, 0, 1,
+      0xff, 0xff, 0xff, 0xff,
+      0, 0, 0, 4, 0, 0, 0, 3,
+      0, 0, 0, 4, 0, 0, 0, 4,
+    ]);
+    assert_eq!(
+      Value::Array(vec![
+        Value::Array(vec![Value::Int4(1), Value::Null]),
+        Value::Array(vec![Value::Int4(3), Value::Int4(4)]),
+      ]),
+      Value::decode_binary(oid::INT4_ARRAY, raw).unwrap()
+    );
+
+    #[rustfmt::skip]
+    let raw = Bytes::from_static(&[
+      0, 0, 0, 2, // ndim
+      0, 0, 0, 0, // has nulls
+      0, 0, 0, 23, // int4
+      0x7f, 0xff, 0xff, 0xff, 0, 0, 0, 1, // dim 1
+      0x7f, 0xff, 0xff, 0xff, 0, 0, 0, 1, // dim 2
+      0, 0, 0, 4, 0, 0, 0, 1,
+    ]);
+    assert!(Value::decode_binary(oid::INT4_ARRAY, raw).is_err());
+    let err = Value::decode_binary(oid::INT4_ARRAY, Bytes::from_static(&[0, 0, 0, 1])).unwrap_err();
+    assert_eq!(Some(&ProtocolError::UnexpectedEof), ProtocolError::from_io_error(&err));
+    let err = Value::decode_binary(oid::NUMERIC, Bytes::from_static(&[0xff, 0xff, 0, 0, 0, 0, 0, 0])).unwrap_err();
+    assert!(ProtocolError::from_io_error(&err).is_some());
+    let err = Value::decode(oid::TEXT, Format::Text, Some(&Bytes::from_static(&[0xff]))).unwrap_err();
+    assert_eq!(Some(&ProtocolError::InvalidUtf8), ProtocolError::from_io_error(&err));
+  }
+
+  #[test]
+  fn decodes_binary_numerics() {
+    // 12345.678 => digits [1, 2345, 6780], weight 1, dscale 3
+    #[rustfmt::skip]
+    let raw = Bytes::from_static(&[0, 3, 0, 1, 0x40, 0, 0, 3, 0, 1, 0x09, 0x29, 0x1a, 0x7c]);
+    assert_eq!("-12345.678", decode_binary_numeric(raw).unwrap());
+
+    // 0.0012 => digits [12], weight -1, dscale 4
+    let raw = Bytes::from_static(&[0, 1, 0xff, 0xff, 0, 0, 0, 4, 0, 12]);
+    assert_eq!("0.0012", decode_binary_numeric(raw).unwrap());
+
+    // 20000 => digits [2], weight 1, dscale 0
+    let raw = Bytes::from_static(&[0, 1, 0, 1, 0, 0, 0, 0, 0, 2]);
+    assert_eq!("20000", decode_binary_numeric(raw).unwrap());
+
+    let raw = Bytes::from_static(&[0, 0, 0, 0, 0xc0, 0, 0, 0]);
+    assert_eq!("NaN", decode_binary_numeric(raw).unwrap());
+  }
+}
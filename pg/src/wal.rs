@@ -1,121 +1,669 @@
-use std::{
-  fmt, io,
-  str::FromStr,
-  time::{Duration, SystemTime},
-};
+use std::{fmt, io, mem, str::FromStr, sync::Arc, time::Duration};
 
-use bytes::Buf;
-use tokio::io::AsyncWriteExt;
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::{
+  io::{AsyncWriteExt, ReadHalf, WriteHalf},
+  sync::Mutex,
+};
 
-use super::{buf_ext::BufExt, stream::Stream};
+use super::{
+  buf_ext::BufExt,
+  conn::{put_standby_status_update, Connection, ConnectionState},
+  error::ProtocolError,
+  pgoutput::{PgOutputMessage, RelationCache},
+  stream::{read_packet, Stream},
+};
 
 #[derive(Debug)]
 pub struct ReplicationStream {
-  pub(crate) stream: Stream,
-  pub(crate) connect_timeout: Option<Duration>,
-  pub(crate) read_timeout: Option<Duration>,
-  pub(crate) write_timeout: Option<Duration>,
+  pub(crate) conn: Connection,
+  pub(crate) slot: String,
+  pub(crate) output_plugin: OutputPlugin,
+  pub(crate) relations: RelationCache,
+  pub(crate) timeline: u32,
+  pub(crate) flushed: Lsn,
+  pub(crate) reconnect_policy: Option<ReconnectPolicy>,
 }
 
 impl ReplicationStream {
+  // Reconnects with the given policy when the connection is lost, instead of ending the stream with an error.
+  pub fn with_reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+    self.reconnect_policy = Some(reconnect_policy);
+    self
+  }
+
   pub async fn recv(&mut self) -> Option<io::Result<ReplicationEvent>> {
-    // TODO: handle disconnects and reconnect here...
-    Some(self.read_replication_event().await)
+    match self.read_replication_event().await {
+      Err(err) if is_disconnect(&err) && self.reconnect_policy.is_some() => Some(self.reconnect(err).await),
+      result => result.transpose(),
+    }
   }
 
-  pub async fn write_status_update(&mut self, lsn: i64) -> io::Result<()> {
-    // TODO: maybe support splitting the receiver from the sender...
+  pub async fn write_status_update(&mut self, lsn: Lsn) -> io::Result<()> {
     self.write_status_update2(lsn, lsn, lsn).await
   }
 
+  // Splits the stream into a reader and a feedback handle, so that events are received on one task while LSNs are
+  // confirmed from others, e.g. once a downstream sink acknowledged them.
+  pub fn split(self) -> (ReplicationReader, ReplicationFeedback) {
+    let write_timeout = self.conn.options().write_timeout;
+    let (reader, writer, state) = self.conn.into_split();
+    let feedback = Arc::new(Mutex::new(FeedbackWriter {
+      stream: Some(writer),
+      write_timeout,
+      flushed: self.flushed,
+    }));
+
+    let reader = ReplicationReader {
+      stream: Some(reader),
+      state: Some(state),
+      slot: self.slot,
+      output_plugin: self.output_plugin,
+      relations: self.relations,
+      timeline: self.timeline,
+      reconnect_policy: self.reconnect_policy,
+      feedback: feedback.clone(),
+    };
+    (reader, ReplicationFeedback { inner: feedback })
+  }
+
   pub async fn close(mut self) -> io::Result<()> {
-    self.stream.shutdown().await
+    self.conn.stream.shutdown().await
+  }
+
+  pub fn output_plugin(&self) -> &OutputPlugin {
+    &self.output_plugin
   }
 
-  async fn read_replication_event(&mut self) -> io::Result<ReplicationEvent> {
-    let (op, mut buffer) = self.stream.read_packet().await?;
+  // Relations received so far when streaming with pgoutput.
+  pub fn relations(&self) -> &RelationCache {
+    &self.relations
+  }
+
+  // Timeline currently being streamed.
+  pub fn timeline(&self) -> u32 {
+    self.timeline
+  }
+
+  async fn read_replication_event(&mut self) -> io::Result<Option<ReplicationEvent>> {
+    let (op, buffer) = self.conn.stream.read_packet().await?;
 
     match op {
-      b'E' => Err(buffer.pg_get_backend_error()),
-      b'N' => Err(buffer.pg_get_backend_notice()),
-      b'd' => {
-        match buffer.get_u8() {
-          b'w' => {
-            let start = buffer.get_i64();
-            let end = buffer.get_i64();
-            let system_clock = buffer.get_i64();
-
-            let data_change = serde_json::from_slice::<DataChange>(buffer.chunk())
-              .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
-
-            Ok(ReplicationEvent::Data {
-              start,
-              end,
-              system_clock,
-              data_change,
-            })
-          }
-          b'k' => {
-            // https://www.postgresql.org/docs/current/protocol-replication.html
-            let end = buffer.get_i64();
-            let system_clock = buffer.get_i64();
-            let must_reply_status = buffer.get_u8();
-
-            Ok(ReplicationEvent::KeepAlive {
-              end,
-              system_clock,
-              must_reply: must_reply_status == 1,
-            })
-          }
-          code => {
-            panic!("Unexpected backend message: {:?}", char::from(code))
-          }
+      b'c' => {
+        // CopyDone (B)
+        // After streaming all the WAL on a timeline that is not the latest one, the server will end streaming by
+        // exiting the COPY mode.
+        self.switch_timeline().await
+      }
+      _ => parse_replication_event(op, buffer, &self.output_plugin, &mut self.relations).map(Some),
+    }
+  }
+
+  // Opens a new connection with the original options and resumes streaming from the last flushed LSN. Changes after
+  // that LSN may be received again. With multiple hosts and `target_session_attrs=primary`, the new connection reaches
+  // the current primary, see `Connection::reconnect`.
+  async fn reconnect(&mut self, err: io::Error) -> io::Result<ReplicationEvent> {
+    let reconnect_policy = self.reconnect_policy.clone().unwrap_or_default();
+    let wal_cursor = WalCursor {
+      timeline: self.timeline,
+      lsn: self.flushed,
+    };
+
+    let mut last_err = err;
+    for attempt in 1..=reconnect_policy.max_attempts {
+      tokio::time::sleep(reconnect_policy.backoff(attempt)).await;
+
+      let conn = async {
+        let mut conn = self.conn.reconnect().await?;
+        conn
+          .start_logical_replication(&self.slot, &wal_cursor, &self.output_plugin)
+          .await?;
+        io::Result::Ok(conn)
+      };
+
+      match conn.await {
+        Ok(conn) => {
+          self.conn = conn;
+          return Ok(ReplicationEvent::Reconnect {
+            lsn: wal_cursor.lsn,
+            attempts: attempt,
+          });
         }
+        Err(err) => last_err = err,
       }
-      code => {
-        panic!("Unexpected backend message: {:?}", char::from(code))
+    }
+
+    Err(last_err)
+  }
+
+  async fn switch_timeline(&mut self) -> io::Result<Option<ReplicationEvent>> {
+    let wal_cursor = match self.conn.read_timeline_switch(self.timeline).await? {
+      Some(wal_cursor) => wal_cursor,
+      // The server left COPY mode without a next timeline, which means the stream is over.
+      None => return Ok(None),
+    };
+
+    self
+      .conn
+      .start_logical_replication(&self.slot, &wal_cursor, &self.output_plugin)
+      .await?;
+    self.timeline = wal_cursor.timeline;
+
+    Ok(Some(ReplicationEvent::ChangeTimeline {
+      timeline: wal_cursor.timeline,
+      lsn: wal_cursor.lsn,
+    }))
+  }
+
+  pub async fn write_status_update2(&mut self, written: Lsn, flushed: Lsn, applied: Lsn) -> io::Result<()> {
+    // Recorded before writing, as a reconnect resumes from it even if the server never received the update.
+    self.flushed = flushed;
+    self
+      .conn
+      .write_standby_status_update(written, flushed, applied, false)
+      .await
+  }
+}
+
+// Receiving half of a split `ReplicationStream`.
+//
+// Switching timelines and reconnecting need the whole connection, so the halves are rejoined for the duration of these
+// operations, during which status updates wait. If `recv` is cancelled in the middle of one of them, the connection is
+// lost and both halves return `NotConnected` errors.
+#[derive(Debug)]
+pub struct ReplicationReader {
+  stream: Option<ReadHalf<Stream>>,
+  state: Option<ConnectionState>,
+  slot: String,
+  output_plugin: OutputPlugin,
+  relations: RelationCache,
+  timeline: u32,
+  reconnect_policy: Option<ReconnectPolicy>,
+  feedback: Arc<Mutex<FeedbackWriter>>,
+}
+
+impl ReplicationReader {
+  pub async fn recv(&mut self) -> Option<io::Result<ReplicationEvent>> {
+    let packet = match self.stream.as_mut() {
+      Some(stream) => read_packet(stream).await,
+      None => Err(not_connected()),
+    };
+
+    match packet {
+      Ok((b'c', _)) => {
+        let mut feedback = self.feedback.clone().lock_owned().await;
+        let mut stream = match self.rejoin(&mut feedback) {
+          Ok(stream) => stream,
+          Err(err) => return Some(Err(err)),
+        };
+        let result = stream.switch_timeline().await;
+        self.resplit(stream, &mut feedback);
+        result.transpose()
+      }
+      Ok((op, buffer)) => Some(parse_replication_event(
+        op,
+        buffer,
+        &self.output_plugin,
+        &mut self.relations,
+      )),
+      Err(err) if is_disconnect(&err) && self.reconnect_policy.is_some() && self.stream.is_some() => {
+        let mut feedback = self.feedback.clone().lock_owned().await;
+        let mut stream = match self.rejoin(&mut feedback) {
+          Ok(stream) => stream,
+          Err(err) => return Some(Err(err)),
+        };
+        let result = stream.reconnect(err).await;
+        self.resplit(stream, &mut feedback);
+        Some(result)
       }
+      Err(err) => Some(Err(err)),
     }
   }
 
-  async fn write_status_update2(&mut self, written: i64, flushed: i64, applied: i64) -> io::Result<()> {
-    let dt = SystemTime::now()
-      .duration_since(SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_800))
-      .unwrap();
+  pub fn output_plugin(&self) -> &OutputPlugin {
+    &self.output_plugin
+  }
+
+  // Relations received so far when streaming with pgoutput.
+  pub fn relations(&self) -> &RelationCache {
+    &self.relations
+  }
+
+  // Timeline currently being streamed.
+  pub fn timeline(&self) -> u32 {
+    self.timeline
+  }
+
+  fn rejoin(&mut self, feedback: &mut FeedbackWriter) -> io::Result<ReplicationStream> {
+    let (reader, writer, state) = match (self.stream.take(), feedback.stream.take(), self.state.take()) {
+      (Some(reader), Some(writer), Some(state)) => (reader, writer, state),
+      _ => return Err(not_connected()),
+    };
 
-    let system_clock = dt.as_micros() as i64;
+    Ok(ReplicationStream {
+      conn: Connection::from_split(reader, writer, state),
+      slot: mem::take(&mut self.slot),
+      output_plugin: self.output_plugin.clone(),
+      relations: mem::take(&mut self.relations),
+      timeline: self.timeline,
+      flushed: feedback.flushed,
+      reconnect_policy: self.reconnect_policy.clone(),
+    })
+  }
 
-    self.stream.write_u8(b'd').await?;
-    self.stream.write_i32(1 + 4 + 8 + 8 + 8 + 8 + 1).await?;
-    self.stream.write_u8(b'r').await?;
-    self.stream.write_i64(written).await?;
-    self.stream.write_i64(flushed).await?;
-    self.stream.write_i64(applied).await?;
-    self.stream.write_i64(system_clock).await?;
-    self.stream.write_u8(0).await?;
-    self.stream.flush().await
+  fn resplit(&mut self, stream: ReplicationStream, feedback: &mut FeedbackWriter) {
+    let (reader, writer, state) = stream.conn.into_split();
+    self.stream = Some(reader);
+    self.state = Some(state);
+    self.slot = stream.slot;
+    self.relations = stream.relations;
+    self.timeline = stream.timeline;
+    feedback.stream = Some(writer);
+    feedback.flushed = stream.flushed;
+  }
+}
+
+// Sending half of a split `ReplicationStream`, which can be cloned and used from any task.
+#[derive(Debug, Clone)]
+pub struct ReplicationFeedback {
+  inner: Arc<Mutex<FeedbackWriter>>,
+}
+
+impl ReplicationFeedback {
+  pub async fn write_status_update(&self, lsn: Lsn) -> io::Result<()> {
+    self.write_status_update2(lsn, lsn, lsn).await
+  }
+
+  // Reports the LSNs written, flushed and applied downstream separately. The server may only discard WAL up to the
+  // flushed LSN, which is also where the reader resumes from after reconnecting.
+  pub async fn write_status_update2(&self, written: Lsn, flushed: Lsn, applied: Lsn) -> io::Result<()> {
+    let mut inner = self.inner.lock().await;
+    inner.flushed = flushed;
+    let write_timeout = inner.write_timeout;
+    let stream = inner.stream.as_mut().ok_or_else(not_connected)?;
+
+    let mut b = BytesMut::new();
+    put_standby_status_update(&mut b, written, flushed, applied, false);
+    let write = async {
+      stream.write_all(&b).await?;
+      stream.flush().await
+    };
+    match write_timeout {
+      Some(duration) => tokio::time::timeout(duration, write)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "write timed out"))
+        .and_then(|r| r),
+      None => write.await,
+    }
   }
 }
 
+#[derive(Debug)]
+struct FeedbackWriter {
+  stream: Option<WriteHalf<Stream>>,
+  write_timeout: Option<Duration>,
+  flushed: Lsn,
+}
+
+fn not_connected() -> io::Error {
+  io::Error::new(io::ErrorKind::NotConnected, "replication stream is not connected")
+}
+
 #[derive(Debug)]
 pub enum ReplicationEvent {
   Data {
-    start: i64,
-    end: i64,
+    start: Lsn,
+    end: Lsn,
     system_clock: i64,
     data_change: DataChange,
   },
+  PgOutput {
+    start: Lsn,
+    end: Lsn,
+    system_clock: i64,
+    message: PgOutputMessage,
+  },
   KeepAlive {
-    end: i64,
+    end: Lsn,
     system_clock: i64,
     must_reply: bool,
   },
   ChangeTimeline {
-    tid: i8,
-    lsn: i64,
+    timeline: u32,
+    lsn: Lsn,
+  },
+  // The connection was lost and streaming resumed from `lsn` after the given number of attempts.
+  Reconnect {
+    lsn: Lsn,
+    attempts: u32,
   },
 }
 
+// How `ReplicationStream::recv` reconnects after losing the connection. Attempts are delayed by an exponential
+// backoff, starting at `initial_backoff` and capped to `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+  pub max_attempts: u32,
+  pub initial_backoff: Duration,
+  pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 10,
+      initial_backoff: Duration::from_millis(100),
+      max_backoff: Duration::from_secs(30),
+    }
+  }
+}
+
+impl ReconnectPolicy {
+  // Delay before the given attempt, starting at 1.
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+    self
+      .initial_backoff
+      .checked_mul(factor)
+      .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+  }
+}
+
+// Parses a message read from a logical replication stream, the payload of CopyData messages included.
+pub fn parse_replication_event(
+  op: u8,
+  mut buffer: Bytes,
+  output_plugin: &OutputPlugin,
+  relations: &mut RelationCache,
+) -> io::Result<ReplicationEvent> {
+  match op {
+    b'E' => Err(buffer.pg_get_backend_error()),
+    b'N' => Err(buffer.pg_get_backend_notice()),
+    b'd' => {
+      match buffer.pg_get_u8()? {
+        b'w' => {
+          let start = Lsn(buffer.pg_get_u64()?);
+          let end = Lsn(buffer.pg_get_u64()?);
+          let system_clock = buffer.pg_get_i64()?;
+
+          match output_plugin {
+            OutputPlugin::Wal2Json => {
+              let data_change = serde_json::from_slice::<DataChange>(buffer.chunk())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+              Ok(ReplicationEvent::Data {
+                start,
+                end,
+                system_clock,
+                data_change,
+              })
+            }
+            OutputPlugin::PgOutput { .. } => {
+              let message = PgOutputMessage::parse(buffer)?;
+
+              if let PgOutputMessage::Relation(relation) = &message {
+                relations.insert(relation.clone());
+              }
+
+              Ok(ReplicationEvent::PgOutput {
+                start,
+                end,
+                system_clock,
+                message,
+              })
+            }
+          }
+        }
+        b'k' => {
+          // https://www.postgresql.org/docs/current/protocol-replication.html
+          let end = Lsn(buffer.pg_get_u64()?);
+          let system_clock = buffer.pg_get_i64()?;
+          let must_reply_status = buffer.pg_get_u8()?;
+
+          Ok(ReplicationEvent::KeepAlive {
+            end,
+            system_clock,
+            must_reply: must_reply_status == 1,
+          })
+        }
+        code => Err(ProtocolError::UnexpectedMessage(code).into()),
+      }
+    }
+    code => Err(ProtocolError::UnexpectedMessage(code).into()),
+  }
+}
+
+fn is_disconnect(err: &io::Error) -> bool {
+  matches!(
+    err.kind(),
+    io::ErrorKind::UnexpectedEof
+      | io::ErrorKind::ConnectionReset
+      | io::ErrorKind::ConnectionAborted
+      | io::ErrorKind::BrokenPipe
+      | io::ErrorKind::NotConnected
+      | io::ErrorKind::TimedOut
+  )
+}
+
+// Streams raw WAL from a physical replication slot (or from a position when no slot is used), as pg_receivewal and
+// standbys do.
+#[derive(Debug)]
+pub struct PhysicalReplicationStream {
+  pub(crate) conn: Connection,
+  pub(crate) slot: Option<String>,
+  pub(crate) timeline: u32,
+}
+
+impl PhysicalReplicationStream {
+  pub async fn recv(&mut self) -> Option<io::Result<PhysicalReplicationEvent>> {
+    self.read_replication_event().await.transpose()
+  }
+
+  pub async fn write_status_update(&mut self, lsn: Lsn) -> io::Result<()> {
+    self.write_status_update2(lsn, lsn, lsn).await
+  }
+
+  // Reports the positions written, flushed and applied by the standby. Only the flushed position is used by the server
+  // to advance the slot.
+  pub async fn write_status_update2(&mut self, written: Lsn, flushed: Lsn, applied: Lsn) -> io::Result<()> {
+    self
+      .conn
+      .write_standby_status_update(written, flushed, applied, false)
+      .await
+  }
+
+  // Asks the server to reply with a keepalive right away, which is handy to measure lag.
+  pub async fn request_reply(&mut self, written: Lsn, flushed: Lsn, applied: Lsn) -> io::Result<()> {
+    self
+      .conn
+      .write_standby_status_update(written, flushed, applied, true)
+      .await
+  }
+
+  pub async fn write_hot_standby_feedback(&mut self, feedback: &HotStandbyFeedback) -> io::Result<()> {
+    self.conn.write_hot_standby_feedback(feedback).await
+  }
+
+  pub async fn close(mut self) -> io::Result<()> {
+    self.conn.stream.shutdown().await
+  }
+
+  pub fn slot(&self) -> Option<&str> {
+    self.slot.as_deref()
+  }
+
+  // Timeline currently being streamed.
+  pub fn timeline(&self) -> u32 {
+    self.timeline
+  }
+
+  async fn read_replication_event(&mut self) -> io::Result<Option<PhysicalReplicationEvent>> {
+    let (op, mut buffer) = self.conn.stream.read_packet().await?;
+
+    match op {
+      b'E' => Err(buffer.pg_get_backend_error()),
+      b'N' => Err(buffer.pg_get_backend_notice()),
+      b'c' => self.switch_timeline().await,
+      b'd' => match buffer.pg_get_u8()? {
+        b'w' => {
+          // XLogData (B)
+          //     Byte1('w')
+          //         Identifies the message as WAL data.
+          //     Int64
+          //         The starting point of the WAL data in this message.
+          //     Int64
+          //         The current end of WAL on the server.
+          //     Int64
+          //         The server's system clock at the time of transmission, as microseconds since midnight on 2000-01-01.
+          //     Byten
+          //         A section of the WAL data stream.
+          let start = Lsn(buffer.pg_get_u64()?);
+          let end = Lsn(buffer.pg_get_u64()?);
+          let system_clock = buffer.pg_get_i64()?;
+
+          Ok(Some(PhysicalReplicationEvent::XLogData {
+            start,
+            end,
+            system_clock,
+            data: buffer,
+          }))
+        }
+        b'k' => {
+          let end = Lsn(buffer.pg_get_u64()?);
+          let system_clock = buffer.pg_get_i64()?;
+          let must_reply_status = buffer.pg_get_u8()?;
+
+          Ok(Some(PhysicalReplicationEvent::KeepAlive {
+            end,
+            system_clock,
+            must_reply: must_reply_status == 1,
+          }))
+        }
+        code => Err(ProtocolError::UnexpectedMessage(code).into()),
+      },
+      code => Err(ProtocolError::UnexpectedMessage(code).into()),
+    }
+  }
+
+  async fn switch_timeline(&mut self) -> io::Result<Option<PhysicalReplicationEvent>> {
+    let wal_cursor = match self.conn.read_timeline_switch(self.timeline).await? {
+      Some(wal_cursor) => wal_cursor,
+      None => return Ok(None),
+    };
+
+    self
+      .conn
+      .start_physical_replication(self.slot.as_deref(), &wal_cursor)
+      .await?;
+    self.timeline = wal_cursor.timeline;
+
+    Ok(Some(PhysicalReplicationEvent::ChangeTimeline {
+      timeline: wal_cursor.timeline,
+      lsn: wal_cursor.lsn,
+    }))
+  }
+}
+
+#[derive(Debug)]
+pub enum PhysicalReplicationEvent {
+  XLogData {
+    start: Lsn,
+    end: Lsn,
+    system_clock: i64,
+    data: Bytes,
+  },
+  KeepAlive {
+    end: Lsn,
+    system_clock: i64,
+    must_reply: bool,
+  },
+  ChangeTimeline {
+    timeline: u32,
+    lsn: Lsn,
+  },
+}
+
+// Transaction ids still needed by queries running on a standby, so the primary does not vacuum them away.
+// Sending all zeroes turns the feedback off.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HotStandbyFeedback {
+  pub xmin: u32,
+  pub xmin_epoch: u32,
+  pub catalog_xmin: u32,
+  pub catalog_xmin_epoch: u32,
+}
+
+// Logical decoding output plugin used by a replication slot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputPlugin {
+  // https://github.com/eulerto/wal2json, using format-version 2.
+  Wal2Json,
+  // https://www.postgresql.org/docs/current/protocol-logical-replication.html
+  PgOutput {
+    proto_version: u32,
+    publication_names: Vec<String>,
+    messages: bool,
+  },
+}
+
+impl OutputPlugin {
+  pub fn pgoutput(publication_names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+    Self::PgOutput {
+      proto_version: 1,
+      publication_names: publication_names.into_iter().map(Into::into).collect(),
+      messages: false,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Wal2Json => "wal2json",
+      Self::PgOutput { .. } => "pgoutput",
+    }
+  }
+
+  // Options passed to START_REPLICATION, e.g. `("format-version" '2')`.
+  pub(crate) fn options(&self) -> String {
+    fn quote_literal(v: &str) -> String {
+      format!("'{}'", v.replace('\'', "''"))
+    }
+
+    fn quote_identifier(v: &str) -> String {
+      format!("\"{}\"", v.replace('"', "\"\""))
+    }
+
+    let mut options = Vec::new();
+    match self {
+      Self::Wal2Json => {
+        options.push(("format-version", "2".to_string()));
+      }
+      Self::PgOutput {
+        proto_version,
+        publication_names,
+        messages,
+      } => {
+        let publication_names = publication_names
+          .iter()
+          .map(|v| quote_identifier(v))
+          .collect::<Vec<_>>()
+          .join(",");
+        options.push(("proto_version", proto_version.to_string()));
+        options.push(("publication_names", publication_names));
+        if *messages {
+          options.push(("messages", "true".to_string()));
+        }
+      }
+    }
+
+    let options = options
+      .into_iter()
+      .map(|(k, v)| format!("{} {}", quote_identifier(k), quote_literal(&v)))
+      .collect::<Vec<_>>()
+      .join(", ");
+    format!("({})", options)
+  }
+}
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(tag = "action")]
 pub enum DataChange {
@@ -166,15 +714,58 @@ pub struct ColumnChange {
   pub value: serde_json::Value,
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
+// Position in the WAL, formatted as `<X>/<Y>` like pg_lsn (e.g. `16/B374D848`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lsn(pub u64);
+
+impl Lsn {
+  pub fn is_zero(&self) -> bool {
+    self.0 == 0
+  }
+}
+
+impl From<u64> for Lsn {
+  fn from(v: u64) -> Self {
+    Self(v)
+  }
+}
+
+impl From<Lsn> for u64 {
+  fn from(v: Lsn) -> Self {
+    v.0
+  }
+}
+
+impl fmt::Display for Lsn {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:X}/{:X}", self.0 >> 32, self.0 & 0xFFFF_FFFF)
+  }
+}
+
+impl FromStr for Lsn {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (hi, lo) = s
+      .split_once('/')
+      .ok_or_else(|| "Failed to parse lsn. Expected format is <X>/<Y>".to_string())?;
+    let hi =
+      u32::from_str_radix(hi, 16).map_err(|_| "Failed to parse lsn. Expected <X> to be u32 hex encoded".to_string())?;
+    let lo =
+      u32::from_str_radix(lo, 16).map_err(|_| "Failed to parse lsn. Expected <Y> to be u32 hex encoded".to_string())?;
+    Ok(Self((u64::from(hi) << 32) | u64::from(lo)))
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct WalCursor {
-  pub tid: i8,
-  pub lsn: i64,
+  pub timeline: u32,
+  pub lsn: Lsn,
 }
 
 impl fmt::Display for WalCursor {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{}/{:X}", self.tid, self.lsn)
+    write!(f, "{}/{}", self.timeline, self.lsn)
   }
 }
 
@@ -182,14 +773,92 @@ impl FromStr for WalCursor {
   type Err = String;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
-    let (tid, lsn) = s
+    let (timeline, lsn) = s
       .split_once('/')
-      .ok_or_else(|| "Failed to parse wal cursor. Expected format is <tid>/<lsn>".to_string())?;
-    let tid = tid
+      .ok_or_else(|| "Failed to parse wal cursor. Expected format is <timeline>/<lsn>".to_string())?;
+    let timeline = timeline
       .parse()
-      .map_err(|_| "Failed to parse wal cursor tid. Expected format is i8.".to_string())?;
-    let lsn = i64::from_str_radix(lsn, 16)
-      .map_err(|_| "Failed to parse wal cursor lsn. Expected format is i64 hex encoded".to_string())?;
-    Ok(Self { tid, lsn })
+      .map_err(|_| "Failed to parse wal cursor timeline. Expected format is u32.".to_string())?;
+    let lsn = lsn.parse()?;
+    Ok(Self { timeline, lsn })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use bytes::Bytes;
+
+  use super::{parse_replication_event, Lsn, OutputPlugin, ProtocolError, ReconnectPolicy, RelationCache, WalCursor};
+
+  #[test]
+  fn reconnect_policy_backoff() {
+    let policy = ReconnectPolicy {
+      max_attempts: 100,
+      initial_backoff: Duration::from_millis(100),
+      max_backoff: Duration::from_secs(1),
+    };
+    assert_eq!(Duration::from_millis(100), policy.backoff(1));
+    assert_eq!(Duration::from_millis(200), policy.backoff(2));
+    assert_eq!(Duration::from_millis(800), policy.backoff(4));
+    assert_eq!(Duration::from_secs(1), policy.backoff(5));
+    assert_eq!(Duration::from_secs(1), policy.backoff(64));
+  }
+
+  #[test]
+  fn rejects_malformed_replication_events() {
+    let output_plugin = OutputPlugin::PgOutput {
+      proto_version: 1,
+      publication_names: vec![],
+      messages: false,
+    };
+    let mut relations = RelationCache::default();
+    let mut parse = |op, raw: &'static [u8]| {
+      let err = parse_replication_event(op, Bytes::from_static(raw), &output_plugin, &mut relations).unwrap_err();
+      ProtocolError::from_io_error(&err).cloned()
+    };
+
+    assert_eq!(Some(ProtocolError::UnexpectedMessage(b'Z')), parse(b'Z', b"I"));
+    assert_eq!(Some(ProtocolError::UnexpectedMessage(b'x')), parse(b'd', b"x"));
+    assert_eq!(Some(ProtocolError::UnexpectedEof), parse(b'd', b""));
+    assert_eq!(
+      Some(ProtocolError::UnexpectedEof),
+      parse(b'd', b"k\x00\x00\x00\x00\x01\x6a")
+    );
+    assert_eq!(
+      Some(ProtocolError::UnexpectedEof),
+      parse(
+        b'd',
+        b"w\x00\x00\x00\x00\x01\x6a\x8f\x48\x00\x00\x00\x00\x01\x6a\x8f\x48"
+      )
+    );
+  }
+
+  #[test]
+  fn parses_lsn() {
+    let lsn = "16/B374D848".parse::<Lsn>().unwrap();
+    assert_eq!(Lsn(0x16_B374D848), lsn);
+    assert_eq!("16/B374D848", lsn.to_string());
+    assert_eq!("0/0", Lsn::default().to_string());
+    assert!("16B374D848".parse::<Lsn>().is_err());
+    assert!("1/FFFFFFFFF".parse::<Lsn>().is_err());
+  }
+
+  #[test]
+  fn parses_wal_cursor() {
+    let wal_cursor = "2/0/16A8F48".parse::<WalCursor>().unwrap();
+    assert_eq!(2, wal_cursor.timeline);
+    assert_eq!(Lsn(0x16A8F48), wal_cursor.lsn);
+    assert_eq!("2/0/16A8F48", wal_cursor.to_string());
+    assert!(
+      WalCursor {
+        timeline: 1,
+        lsn: Lsn(2)
+      } < WalCursor {
+        timeline: 2,
+        lsn: Lsn(1)
+      }
+    );
   }
 }
@@ -1,11 +1,15 @@
 use std::time::Duration;
 
-use clap::{Arg, Command};
+use clap::{value_parser, Arg, ArgAction, Command};
+use tokio::sync::{mpsc, watch};
 use url::Url;
 
 use pg::{
-  conn::Connection,
-  wal::{ColumnChange, DataChange, ReplicationEvent, WalCursor},
+  conn::{parse_url, Connection},
+  pgoutput::{PgOutputMessage, RelationCache, TupleData, TupleValue},
+  snapshot::{Snapshot, SnapshotTable},
+  types::Value,
+  wal::{ColumnChange, DataChange, OutputPlugin, ReconnectPolicy, ReplicationEvent, WalCursor},
 };
 use sink::{Column, ColumnType, ColumnValue, RowEvent};
 
@@ -14,57 +18,242 @@ async fn main() {
   let mut cmd = Command::new("pg")
     .version("1.0")
     .author("Maxime Bedard <maxime@bedard.dev>")
-    .arg(Arg::new("url").required(true).short('u').value_parser(Url::parse))
+    .arg(Arg::new("url").required(true).short('u').value_parser(parse_url))
     .arg(Arg::new("slot").required(true))
-    .arg(Arg::new("wal-cursor").value_parser(str::parse::<WalCursor>));
+    .arg(
+      Arg::new("wal-cursor")
+        .value_parser(str::parse::<WalCursor>)
+        .conflicts_with("snapshot"),
+    )
+    .arg(
+      Arg::new("snapshot")
+        .long("snapshot")
+        .action(ArgAction::SetTrue)
+        .help("Creates the slot and reads every table before streaming changes"),
+    )
+    .arg(
+      Arg::new("snapshot-workers")
+        .long("snapshot-workers")
+        .default_value("1")
+        .value_parser(value_parser!(u16).range(1..)),
+    )
+    .arg(
+      Arg::new("max-reconnect-attempts")
+        .long("max-reconnect-attempts")
+        .default_value("10")
+        .value_parser(value_parser!(u32))
+        .help("Reconnects when the connection is lost, 0 disables reconnects"),
+    )
+    .arg(
+      Arg::new("output-plugin")
+        .long("output-plugin")
+        .default_value("wal2json")
+        .value_parser(["wal2json", "pgoutput"]),
+    )
+    .arg(
+      Arg::new("publication")
+        .long("publication")
+        .action(ArgAction::Append)
+        .required_if_eq("output-plugin", "pgoutput"),
+    );
 
   let mut matches = cmd.get_matches_mut();
 
-  let url = matches.remove_one::<Url>("url").unwrap();
+  let mut url = matches.remove_one::<Url>("url").unwrap();
+  // Streaming changes requires a logical replication connection, unless the url asks for another mode.
+  if !url.query_pairs().any(|(name, _)| name == "replication") {
+    url.query_pairs_mut().append_pair("replication", "database");
+  }
   let slot = matches.remove_one::<String>("slot").unwrap();
   let wal_cursor = matches.remove_one::<WalCursor>("wal-cursor");
+  let snapshot = matches.get_flag("snapshot");
+  let snapshot_workers = matches.remove_one::<u16>("snapshot-workers").unwrap();
+  let max_reconnect_attempts = matches.remove_one::<u32>("max-reconnect-attempts").unwrap();
+  let output_plugin = match matches.remove_one::<String>("output-plugin").unwrap().as_str() {
+    "pgoutput" => OutputPlugin::pgoutput(matches.remove_many::<String>("publication").unwrap()),
+    _ => OutputPlugin::Wal2Json,
+  };
 
   let mut conn_pg = Connection::connect_from_url(&url).await.unwrap();
 
-  let wal_cursor = match wal_cursor {
-    Some(wal_cursor) => wal_cursor,
-    None => conn_pg.identify_system().await.unwrap().wal_cursor,
+  let (mut stream, wal_cursor) = if snapshot {
+    let snapshot = Snapshot::create(conn_pg, slot, output_plugin).await.unwrap();
+    read_snapshot(&snapshot, snapshot_workers.into()).await;
+    let wal_cursor = snapshot.wal_cursor();
+    (snapshot.start_replication_stream().await.unwrap(), wal_cursor)
+  } else {
+    let wal_cursor = match wal_cursor {
+      Some(wal_cursor) => wal_cursor,
+      None => conn_pg.identify_system().await.unwrap().wal_cursor,
+    };
+    let stream = conn_pg
+      .start_replication_stream(slot, wal_cursor.clone(), output_plugin)
+      .await
+      .unwrap();
+    (stream, wal_cursor)
   };
 
-  let mut stream = conn_pg
-    .start_replication_stream(slot, wal_cursor.clone())
-    .await
-    .unwrap();
+  if max_reconnect_attempts > 0 {
+    stream = stream.with_reconnect_policy(ReconnectPolicy {
+      max_attempts: max_reconnect_attempts,
+      ..Default::default()
+    });
+  }
 
+  let (mut reader, feedback) = stream.split();
+  let (flushed_tx, flushed_rx) = watch::channel(wal_cursor.lsn);
   let mut processor = EventProcessor { wal_cursor };
 
+  // Status updates are written from their own task, once events up to the LSN were handed to the sink. The default
+  // healthcheck is configured to 10s.
+  let status_updates = tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    loop {
+      interval.tick().await;
+      let lsn = *flushed_rx.borrow();
+      // A lost connection is reported, and possibly recovered, by the reader.
+      if let Err(err) = feedback.write_status_update(lsn).await {
+        eprintln!("failed to write status update: {}", err);
+      }
+    }
+  });
+
   let interrupt = tokio::signal::ctrl_c();
   tokio::pin!(interrupt);
 
-  // default healthcheck is configured to 10s.
-  let mut interval = tokio::time::interval(Duration::from_secs(10));
-
   loop {
     tokio::select! {
       Ok(_) = &mut interrupt => break,
-      event = stream.recv() => {
+      event = reader.recv() => {
         match event {
           Some(Ok(event)) => {
-            if let Some(event) = processor.process_event(event) {
+            if let Some(event) = processor.process_event(event, reader.relations()) {
               println!("{:?}", event);
             }
+            flushed_tx.send_replace(processor.wal_cursor.lsn);
           },
           Some(Err(err)) => panic!("{}", err),
           None => break,
         }
       },
-      _ = interval.tick() => {
-        stream.write_status_update(processor.wal_cursor.lsn).await.unwrap();
-      },
     }
   }
 
-  stream.close().await.unwrap();
+  status_updates.abort();
+}
+
+// Reads every table as of the snapshot, spreading the tables across workers.
+async fn read_snapshot(snapshot: &Snapshot, workers_len: usize) {
+  let mut workers = Vec::with_capacity(workers_len);
+  for _ in 0..workers_len {
+    workers.push(snapshot.open_worker().await.unwrap());
+  }
+
+  let mut tables = Vec::new();
+  for name in workers[0].list_tables().await.unwrap() {
+    tables.push(workers[0].describe_table(&name).await.unwrap());
+  }
+
+  let (tx, mut rx) = mpsc::channel(1024);
+  let mut handles = Vec::with_capacity(workers_len);
+  for (i, mut worker) in workers.into_iter().enumerate() {
+    let tables = tables.iter().skip(i).step_by(workers_len).cloned().collect::<Vec<_>>();
+    let tx = tx.clone();
+    handles.push(tokio::spawn(async move {
+      for table in &tables {
+        let mut reader = worker.read_table(table).await?;
+        while let Some(values) = reader.recv().await {
+          if tx.send(map_snapshot_row(table, values?)).await.is_err() {
+            break;
+          }
+        }
+      }
+      worker.finish().await
+    }));
+  }
+  drop(tx);
+
+  while let Some(event) = rx.recv().await {
+    println!("{:?}", event);
+  }
+
+  for handle in handles {
+    handle.await.unwrap().unwrap();
+  }
+}
+
+fn map_snapshot_row(table: &SnapshotTable, values: Vec<Value>) -> RowEvent {
+  let columns = table
+    .columns
+    .iter()
+    .zip(values)
+    .map(|(c, v)| {
+      let (column_type, value) = map_value(v);
+      Column {
+        name: c.name.clone(),
+        column_type,
+        is_nullable: c.is_nullable,
+        value,
+      }
+    })
+    .collect();
+  RowEvent::Insert {
+    schema: table.name.schema.clone(),
+    table: table.name.name.clone(),
+    columns,
+  }
+}
+
+fn map_value(value: Value) -> (ColumnType, ColumnValue) {
+  match value {
+    Value::Null => (ColumnType::String, ColumnValue::Null),
+    Value::Bool(v) => (ColumnType::U64, ColumnValue::U64(v.into())),
+    Value::Int2(v) => (ColumnType::I64, ColumnValue::I64(v.into())),
+    Value::Int4(v) => (ColumnType::I64, ColumnValue::I64(v.into())),
+    Value::Int8(v) => (ColumnType::I64, ColumnValue::I64(v)),
+    Value::Oid(v) => (ColumnType::U64, ColumnValue::U64(v.into())),
+    Value::Float4(v) => (ColumnType::F64, ColumnValue::F64(v.into())),
+    Value::Float8(v) => (ColumnType::F64, ColumnValue::F64(v)),
+    Value::Numeric(v) => (ColumnType::Decimal, ColumnValue::String(v)),
+    Value::Text(v) => (ColumnType::String, ColumnValue::String(v)),
+    Value::Bytea(v) => (ColumnType::Bytes, ColumnValue::Bytes(v)),
+    Value::Uuid(v) => (ColumnType::String, ColumnValue::String(v.to_string())),
+    Value::Json(v) => (ColumnType::Json, ColumnValue::String(v.to_string())),
+    Value::Date(v) => (ColumnType::Date, ColumnValue::String(v.to_string())),
+    Value::Time(v) => (ColumnType::Time, ColumnValue::String(v.to_string())),
+    Value::Timestamp(v) => (ColumnType::Timestamp, ColumnValue::String(v.to_string())),
+    Value::TimestampTz(v) => (ColumnType::Timestamp, ColumnValue::String(v.to_rfc3339())),
+    Value::Interval(v) => (
+      ColumnType::String,
+      ColumnValue::String(format!(
+        "{} months {} days {} microseconds",
+        v.months, v.days, v.microseconds
+      )),
+    ),
+    Value::Array(v) => (ColumnType::Json, ColumnValue::String(array_to_json(v).to_string())),
+    Value::Unknown { raw, .. } => (ColumnType::Bytes, ColumnValue::Bytes(raw)),
+  }
+}
+
+fn array_to_json(values: Vec<Value>) -> serde_json::Value {
+  serde_json::Value::Array(
+    values
+      .into_iter()
+      .map(|v| match v {
+        Value::Array(v) => array_to_json(v),
+        Value::Json(v) => v,
+        Value::Null => serde_json::Value::Null,
+        v => match map_value(v).1 {
+          ColumnValue::Null => serde_json::Value::Null,
+          ColumnValue::U64(v) => v.into(),
+          ColumnValue::I64(v) => v.into(),
+          ColumnValue::F64(v) => v.into(),
+          ColumnValue::String(v) => v.into(),
+          ColumnValue::Bytes(v) => v.iter().map(|b| format!("{:02x}", b)).collect::<String>().into(),
+        },
+      })
+      .collect(),
+  )
 }
 
 struct EventProcessor {
@@ -72,7 +261,7 @@ struct EventProcessor {
 }
 
 impl EventProcessor {
-  fn process_event(&mut self, event: ReplicationEvent) -> Option<RowEvent> {
+  fn process_event(&mut self, event: ReplicationEvent, relations: &RelationCache) -> Option<RowEvent> {
     fn map_column_change(column_changes: Vec<ColumnChange>) -> Vec<Column> {
       column_changes
         .into_iter()
@@ -90,6 +279,35 @@ impl EventProcessor {
         .collect()
     }
 
+    fn map_tuple_data(
+      relations: &RelationCache,
+      relation_id: i32,
+      tuple_data: TupleData,
+    ) -> (String, String, Vec<Column>) {
+      let relation = relations
+        .get(relation_id)
+        .expect("relation message must precede row changes");
+      let columns = relation
+        .columns
+        .iter()
+        .zip(tuple_data)
+        .map(|(c, v)| {
+          let value = match v {
+            TupleValue::Text(v) => ColumnValue::String(v),
+            TupleValue::Binary(v) => ColumnValue::Bytes(v),
+            TupleValue::Null | TupleValue::UnchangedToast => ColumnValue::Null,
+          };
+          Column {
+            name: c.name.clone(),
+            column_type: ColumnType::String,
+            is_nullable: !c.is_key,
+            value,
+          }
+        })
+        .collect();
+      (relation.namespace.clone(), relation.name.clone(), columns)
+    }
+
     match event {
       ReplicationEvent::Data { end, data_change, .. } => {
         self.wal_cursor.lsn = end;
@@ -131,12 +349,51 @@ impl EventProcessor {
           DataChange::Commit => None,
         }
       }
+      ReplicationEvent::PgOutput { end, message, .. } => {
+        self.wal_cursor.lsn = end;
+        match message {
+          PgOutputMessage::Insert { relation_id, new } => {
+            let (schema, table, columns) = map_tuple_data(relations, relation_id, new);
+            Some(RowEvent::Insert { schema, table, columns })
+          }
+          PgOutputMessage::Update {
+            relation_id,
+            identity,
+            new,
+          } => {
+            let (schema, table, columns) = map_tuple_data(relations, relation_id, new);
+            let identity = match identity {
+              Some(identity) => map_tuple_data(relations, relation_id, identity.into_tuple_data()).2,
+              None => Vec::new(),
+            };
+            Some(RowEvent::Update {
+              schema,
+              table,
+              columns,
+              identity,
+            })
+          }
+          PgOutputMessage::Delete { relation_id, identity } => {
+            let (schema, table, identity) = map_tuple_data(relations, relation_id, identity.into_tuple_data());
+            Some(RowEvent::Delete {
+              schema,
+              table,
+              identity,
+            })
+          }
+          _ => None,
+        }
+      }
       ReplicationEvent::KeepAlive { end, .. } => {
         self.wal_cursor.lsn = end;
         None
       }
-      ReplicationEvent::ChangeTimeline { tid, lsn } => {
-        self.wal_cursor.tid = tid;
+      ReplicationEvent::ChangeTimeline { timeline, lsn } => {
+        self.wal_cursor.timeline = timeline;
+        self.wal_cursor.lsn = lsn;
+        None
+      }
+      ReplicationEvent::Reconnect { lsn, .. } => {
         self.wal_cursor.lsn = lsn;
         None
       }
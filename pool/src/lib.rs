@@ -0,0 +1,388 @@
+use std::{
+  collections::VecDeque,
+  fmt,
+  future::Future,
+  io,
+  ops::{Deref, DerefMut},
+  pin::Pin,
+  sync::{Arc, Mutex, Weak},
+  time::Duration,
+};
+
+use tokio::{
+  sync::{OwnedSemaphorePermit, Semaphore},
+  time::Instant,
+};
+use url::Url;
+
+type ConnectFn<M> = dyn Fn() -> Pin<Box<dyn Future<Output = io::Result<M>> + Send>> + Send + Sync;
+
+// Connection that can be pooled, e.g. a pg or mysql `Connection`.
+pub trait Manageable: Sized + Send + 'static {
+  fn connect(url: &Url) -> impl Future<Output = io::Result<Self>> + Send;
+
+  #[cfg(feature = "ssl")]
+  fn connect_ssl(url: &Url, ssl_connector: openssl::ssl::SslConnector) -> impl Future<Output = io::Result<Self>> + Send;
+
+  // Checks that the connection is still usable, see `PoolOptions::test_on_checkout`.
+  fn ping(&mut self) -> impl Future<Output = io::Result<()>> + Send;
+
+  fn close(self) -> impl Future<Output = io::Result<()>> + Send;
+
+  // Whether the connection can be handed out again once returned. It must not be when a request did not complete,
+  // e.g. its future was dropped or it failed mid-response, as the next user would read the rest of the response, or
+  // when a transaction is still open.
+  fn is_reusable(&self) -> bool;
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+  // Maximum number of connections, idle or checked out.
+  pub max_size: usize,
+  // How long `get` waits for a connection, including the time to open a new one.
+  pub checkout_timeout: Option<Duration>,
+  // Idle connections are closed after this duration.
+  pub idle_timeout: Option<Duration>,
+  // Connections are closed once they are this old, even if they are used.
+  pub max_lifetime: Option<Duration>,
+  // Pings idle connections before handing them out, and discards the ones that fail.
+  pub test_on_checkout: bool,
+}
+
+impl Default for PoolOptions {
+  fn default() -> Self {
+    Self {
+      max_size: 10,
+      checkout_timeout: Some(Duration::from_secs(30)),
+      idle_timeout: Some(Duration::from_secs(10 * 60)),
+      max_lifetime: Some(Duration::from_secs(30 * 60)),
+      test_on_checkout: true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+  pub idle: usize,
+  // Connections checked out, or being opened.
+  pub in_use: usize,
+}
+
+// Bounded pool of connections opened on demand. Cloning the pool is cheap and shares the connections.
+//
+// Idle and expired connections are closed by a background task, so the pool must be created within a tokio runtime.
+pub struct Pool<M: Manageable> {
+  inner: Arc<PoolInner<M>>,
+}
+
+struct PoolInner<M: Manageable> {
+  options: PoolOptions,
+  connect: Box<ConnectFn<M>>,
+  idle: Mutex<VecDeque<IdleConnection<M>>>,
+  semaphore: Arc<Semaphore>,
+}
+
+struct IdleConnection<M> {
+  conn: M,
+  created_at: Instant,
+  idle_since: Instant,
+}
+
+impl<M: Manageable> Pool<M> {
+  pub fn new<F, Fut>(options: PoolOptions, connect: F) -> Self
+  where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = io::Result<M>> + Send + 'static,
+  {
+    let inner = Arc::new(PoolInner {
+      semaphore: Arc::new(Semaphore::new(options.max_size)),
+      idle: Mutex::new(VecDeque::new()),
+      connect: Box::new(move || Box::pin(connect())),
+      options,
+    });
+
+    let period = [inner.options.idle_timeout, inner.options.max_lifetime]
+      .into_iter()
+      .flatten()
+      .min();
+    if let Some(period) = period {
+      tokio::spawn(reap(Arc::downgrade(&inner), period));
+    }
+
+    Self { inner }
+  }
+
+  pub fn from_url(url: &Url, options: PoolOptions) -> Self {
+    let url = url.clone();
+    Self::new(options, move || {
+      let url = url.clone();
+      async move { M::connect(&url).await }
+    })
+  }
+
+  #[cfg(feature = "ssl")]
+  pub fn from_ssl_url(url: &Url, ssl_connector: openssl::ssl::SslConnector, options: PoolOptions) -> Self {
+    let url = url.clone();
+    Self::new(options, move || {
+      let url = url.clone();
+      let ssl_connector = ssl_connector.clone();
+      async move { M::connect_ssl(&url, ssl_connector).await }
+    })
+  }
+
+  pub fn options(&self) -> &PoolOptions {
+    &self.inner.options
+  }
+
+  pub fn status(&self) -> PoolStatus {
+    let idle = self.inner.idle.lock().unwrap().len();
+    let in_use = self.inner.options.max_size - self.inner.semaphore.available_permits();
+    PoolStatus { idle, in_use }
+  }
+
+  // Checks out an idle connection, or opens a new one when there is none and the pool is not full. The connection is
+  // returned to the pool when the `PooledConnection` is dropped.
+  pub async fn get(&self) -> io::Result<PooledConnection<M>> {
+    match self.inner.options.checkout_timeout {
+      Some(checkout_timeout) => tokio::time::timeout(checkout_timeout, self.checkout())
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "pool checkout timed out"))
+        .and_then(|r| r),
+      None => self.checkout().await,
+    }
+  }
+
+  async fn checkout(&self) -> io::Result<PooledConnection<M>> {
+    let permit = self
+      .inner
+      .semaphore
+      .clone()
+      .acquire_owned()
+      .await
+      .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "pool is closed"))?;
+
+    loop {
+      let idle = self.inner.idle.lock().unwrap().pop_back();
+      match idle {
+        Some(idle) if self.inner.is_expired(&idle) => {
+          let _ = idle.conn.close().await;
+        }
+        Some(mut idle) => {
+          if self.inner.options.test_on_checkout && idle.conn.ping().await.is_err() {
+            continue;
+          }
+          return Ok(PooledConnection {
+            pool: self.inner.clone(),
+            conn: Some(idle.conn),
+            created_at: idle.created_at,
+            _permit: permit,
+          });
+        }
+        None => {
+          let conn = (self.inner.connect)().await?;
+          return Ok(PooledConnection {
+            pool: self.inner.clone(),
+            conn: Some(conn),
+            created_at: Instant::now(),
+            _permit: permit,
+          });
+        }
+      }
+    }
+  }
+
+  // Closes the idle connections and fails the pending and future checkouts. Connections that are checked out are
+  // closed when they are returned.
+  pub async fn close(&self) {
+    self.inner.semaphore.close();
+    let idle = self.inner.idle.lock().unwrap().drain(..).collect::<Vec<_>>();
+    for idle in idle {
+      let _ = idle.conn.close().await;
+    }
+  }
+}
+
+impl<M: Manageable> Clone for Pool<M> {
+  fn clone(&self) -> Self {
+    Self {
+      inner: self.inner.clone(),
+    }
+  }
+}
+
+impl<M: Manageable> fmt::Debug for Pool<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Pool")
+      .field("options", &self.inner.options)
+      .field("status", &self.status())
+      .finish()
+  }
+}
+
+impl<M: Manageable> PoolInner<M> {
+  fn is_expired(&self, idle: &IdleConnection<M>) -> bool {
+    let now = Instant::now();
+    let idle_expired = self
+      .options
+      .idle_timeout
+      .is_some_and(|timeout| now.duration_since(idle.idle_since) >= timeout);
+    let lifetime_expired = self
+      .options
+      .max_lifetime
+      .is_some_and(|lifetime| now.duration_since(idle.created_at) >= lifetime);
+    idle_expired || lifetime_expired
+  }
+
+  // Connections that are not reusable are dropped, which closes their socket.
+  fn release(&self, conn: M, created_at: Instant) {
+    if self.semaphore.is_closed() || !conn.is_reusable() {
+      return;
+    }
+    self.idle.lock().unwrap().push_back(IdleConnection {
+      conn,
+      created_at,
+      idle_since: Instant::now(),
+    });
+  }
+}
+
+async fn reap<M: Manageable>(pool: Weak<PoolInner<M>>, period: Duration) {
+  let mut interval = tokio::time::interval(period);
+  interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+  loop {
+    interval.tick().await;
+
+    let expired = match pool.upgrade() {
+      Some(pool) if !pool.semaphore.is_closed() => {
+        let mut idle = pool.idle.lock().unwrap();
+        let (expired, kept): (Vec<_>, Vec<_>) = idle.drain(..).partition(|idle| pool.is_expired(idle));
+        *idle = kept.into();
+        expired
+      }
+      _ => return,
+    };
+
+    for idle in expired {
+      let _ = idle.conn.close().await;
+    }
+  }
+}
+
+// Connection checked out of a pool, which dereferences to the underlying connection.
+pub struct PooledConnection<M: Manageable> {
+  pool: Arc<PoolInner<M>>,
+  conn: Option<M>,
+  created_at: Instant,
+  // Released after the connection is back in the pool.
+  _permit: OwnedSemaphorePermit,
+}
+
+impl<M: Manageable> PooledConnection<M> {
+  // Takes the connection out of the pool, e.g. to start a replication stream, which frees its slot.
+  pub fn detach(mut self) -> M {
+    self.conn.take().unwrap()
+  }
+}
+
+impl<M: Manageable> Deref for PooledConnection<M> {
+  type Target = M;
+
+  fn deref(&self) -> &Self::Target {
+    self.conn.as_ref().unwrap()
+  }
+}
+
+impl<M: Manageable> DerefMut for PooledConnection<M> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    self.conn.as_mut().unwrap()
+  }
+}
+
+impl<M: Manageable + fmt::Debug> fmt::Debug for PooledConnection<M> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PooledConnection").field("conn", &self.conn).finish()
+  }
+}
+
+impl<M: Manageable> Drop for PooledConnection<M> {
+  fn drop(&mut self) {
+    if let Some(conn) = self.conn.take() {
+      self.pool.release(conn, self.created_at);
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use super::*;
+
+  #[derive(Debug)]
+  struct TestConnection {
+    id: usize,
+    reusable: bool,
+  }
+
+  impl Manageable for TestConnection {
+    async fn connect(_url: &Url) -> io::Result<Self> {
+      unimplemented!()
+    }
+
+    #[cfg(feature = "ssl")]
+    async fn connect_ssl(_url: &Url, _ssl_connector: openssl::ssl::SslConnector) -> io::Result<Self> {
+      unimplemented!()
+    }
+
+    async fn ping(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+
+    async fn close(self) -> io::Result<()> {
+      Ok(())
+    }
+
+    fn is_reusable(&self) -> bool {
+      self.reusable
+    }
+  }
+
+  fn test_pool() -> Pool<TestConnection> {
+    let ids = Arc::new(AtomicUsize::new(0));
+    Pool::new(
+      PoolOptions {
+        max_size: 2,
+        ..Default::default()
+      },
+      move || {
+        let id = ids.fetch_add(1, Ordering::SeqCst);
+        async move { Ok(TestConnection { id, reusable: true }) }
+      },
+    )
+  }
+
+  #[tokio::test]
+  async fn reuses_returned_connections() {
+    let pool = test_pool();
+    let conn = pool.get().await.unwrap();
+    assert_eq!(0, conn.id);
+    assert_eq!(PoolStatus { idle: 0, in_use: 1 }, pool.status());
+
+    drop(conn);
+    assert_eq!(PoolStatus { idle: 1, in_use: 0 }, pool.status());
+    assert_eq!(0, pool.get().await.unwrap().id);
+  }
+
+  #[tokio::test]
+  async fn discards_connections_that_are_not_reusable() {
+    let pool = test_pool();
+    let mut conn = pool.get().await.unwrap();
+    // e.g. a query was cancelled before its response was read.
+    conn.reusable = false;
+
+    drop(conn);
+    assert_eq!(PoolStatus { idle: 0, in_use: 0 }, pool.status());
+    assert_eq!(1, pool.get().await.unwrap().id);
+  }
+}
@@ -6,7 +6,13 @@ CREATE USER 'sha2_user'@'%' IDENTIFIED WITH caching_sha2_password BY 'password';
 GRANT ALL PRIVILEGES ON test.* TO 'sha2_user'@'%';
 GRANT REPLICATION SLAVE, REPLICATION CLIENT ON *.* TO 'sha2_user'@'%';
 
+-- Only used by test_ping_user_sha2_rsa, so that no other test fills its authentication cache.
+CREATE USER 'sha2_rsa_user'@'%' IDENTIFIED WITH caching_sha2_password BY 'password';
+GRANT ALL PRIVILEGES ON test.* TO 'sha2_rsa_user'@'%';
+
 CREATE USER 'native_user'@'%' IDENTIFIED WITH mysql_native_password BY 'password';
 GRANT ALL PRIVILEGES ON test.* TO 'native_user'@'%';
 GRANT REPLICATION SLAVE, REPLICATION CLIENT ON *.* TO 'native_user'@'%';
+-- FLUSH PRIVILEGES clears the caching_sha2_password authentication cache.
+GRANT RELOAD ON *.* TO 'native_user'@'%';
 SQL
\ No newline at end of file